@@ -1,8 +1,40 @@
-import Oscillator from './src/nes/browser/oscillator.js';
-import Noise from './src/nes/browser/noise.js';
+import Speaker from './src/nes/browser/speaker.js';
 
 let buf = null
 
+// Pixels hidden on each edge of the 256x240 frame.
+// NTSC TVs hide about 8 lines at the top and bottom, set all to 0 to see the whole frame.
+export const config = {
+  overscan: { top: 8, bottom: 8, left: 0, right: 0 },
+  // Per channel controls in the order of square1, square2, triangle, noise and dmc.
+  // They only change what is heard, the emulation is unaffected.
+  mixer: {
+    gains: [1, 1, 1, 1, 1],
+    muted: [false, false, false, false, false],
+    soloed: [false, false, false, false, false],
+  },
+};
+
+const CHANNELS = ['square1', 'square2', 'triangle', 'noise', 'dmc'];
+
+const channelIndex = (name) => {
+  const index = CHANNELS.indexOf(name);
+  if (index < 0) throw new Error(`Unknown channel ${name}`);
+  return index;
+};
+
+export const setChannelGain = (name, gain) => {
+  config.mixer.gains[channelIndex(name)] = gain;
+};
+
+export const setChannelMuted = (name, isMuted) => {
+  config.mixer.muted[channelIndex(name)] = isMuted;
+};
+
+export const setChannelSoloed = (name, isSoloed) => {
+  config.mixer.soloed[channelIndex(name)] = isSoloed;
+};
+
 const convertKeyCode = (keyCode) => {
   switch (keyCode) {
   case 88: return 0x01; // X  A
@@ -40,18 +72,19 @@ const startArrayBuf = (arrayBuf) => {
   const canvas = document.querySelector("canvas");
   const ctx = canvas.getContext('2d');
   if (Module.NES) {
-    Module.NES.oscs.forEach(o => o.close());
-    Module.NES.noise.close();
+    Module.NES.speaker.close();
   }
+  const { overscan } = config;
   Module.NES = {
     ctx,
     canvas,
     image: ctx.createImageData(256, 240),
-    oscs: [new Oscillator(), new Oscillator(), new Oscillator('triangle')],
-    noise: new Noise(),
+    overscan: Object.assign({}, overscan),
+    speaker: new Speaker(),
+    mixer: config.mixer,
   }
-  canvas.width = 256;
-  canvas.height = 240;
+  canvas.width = 256 - overscan.left - overscan.right;
+  canvas.height = 240 - overscan.top - overscan.bottom;
 
   const nes = new Uint8Array(arrayBuf);
   // Add key code area to tail.
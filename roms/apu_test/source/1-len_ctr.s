@@ -0,0 +1,95 @@
+; Length counter of square 1 with $4015 and clocks by $4017 writes.
+;
+; 2) $4003 doesn't load the length or $4015 doesn't report it
+; 3) A clock by $4017 with bit 7 set takes a length of 2 to 0
+; 4) Two clocks don't take a length of 2 to 0
+; 5) Disabling the channel in $4015 doesn't clear the length
+; 6) $4003 loads the length while the channel is disabled
+; 7) The halt flag of $4000 doesn't stop the length
+
+	.include "shell.inc"
+
+name:
+	.db "1-len_ctr", 10, 10, 0
+
+main:
+	lda #$C0		; 5-step mode, the next clock is 14913 cycles later
+	sta $4017
+	lda #$01
+	sta $4015
+	lda #$10
+	sta $4000
+	lda #$18		; length 2
+	sta $4003
+	lda $4015
+	and #$01
+	bne check_3
+	lda #2
+	jmp fail
+
+check_3:
+	lda #$80
+	sta $4017
+	nop
+	nop
+	lda $4015
+	and #$01
+	bne check_4
+	lda #3
+	jmp fail
+
+check_4:
+	lda #$80
+	sta $4017
+	nop
+	nop
+	lda $4015
+	and #$01
+	beq check_5
+	lda #4
+	jmp fail
+
+check_5:
+	lda #$18
+	sta $4003
+	lda #$00
+	sta $4015
+	lda $4015
+	and #$01
+	beq check_6
+	lda #5
+	jmp fail
+
+check_6:
+	lda #$18
+	sta $4003
+	lda $4015
+	and #$01
+	beq check_7
+	lda #6
+	jmp fail
+
+check_7:
+	lda #$01
+	sta $4015
+	lda #$30		; halt
+	sta $4000
+	lda #$18
+	sta $4003
+	lda #$80
+	sta $4017
+	lda #$80
+	sta $4017
+	nop
+	nop
+	lda $4015
+	and #$01
+	bne done
+	lda #7
+	jmp fail
+
+done:
+	jmp pass
+
+	.org $FFFA
+	.dw nmi, reset, irq
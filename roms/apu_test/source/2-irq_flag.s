@@ -0,0 +1,120 @@
+; Frame IRQ flag of $4015 and the IRQ of the frame counter.
+;
+; 2) The flag isn't set after a frame in 4-step mode
+; 3) Reading $4015 doesn't clear the flag
+; 4) Writing $4017 with bit 6 set doesn't clear the flag
+; 5) The flag is set in 5-step mode
+; 6) The flag is set while inhibited by bit 6 of $4017
+; 7) The IRQ isn't taken once per frame when the I flag is clear
+
+	.include "shell.inc"
+
+name:
+	.db "2-irq_flag", 10, 10, 0
+
+main:
+	lda #$40		; inhibited, clears the flag
+	sta $4017
+	lda #$00
+	sta $4017
+	ldy #229		; 30000 cycles
+delay_2_y:
+	ldx #25
+delay_2_x:
+	dex
+	bne delay_2_x
+	dey
+	bne delay_2_y
+	lda $4015
+	and #$40
+	bne check_3
+	lda #2
+	jmp fail
+
+check_3:
+	lda $4015
+	and #$40
+	beq check_4
+	lda #3
+	jmp fail
+
+check_4:
+	lda #$00
+	sta $4017
+	ldy #229		; 30000 cycles
+delay_4_y:
+	ldx #25
+delay_4_x:
+	dex
+	bne delay_4_x
+	dey
+	bne delay_4_y
+	lda #$40
+	sta $4017
+	nop
+	nop
+	lda $4015
+	and #$40
+	beq check_5
+	lda #4
+	jmp fail
+
+check_5:
+	lda #$80
+	sta $4017
+	ldy #199		; 40000 cycles
+delay_5_y:
+	ldx #39
+delay_5_x:
+	dex
+	bne delay_5_x
+	dey
+	bne delay_5_y
+	lda $4015
+	and #$40
+	beq check_6
+	lda #5
+	jmp fail
+
+check_6:
+	lda #$40
+	sta $4017
+	ldy #229		; 30000 cycles
+delay_6_y:
+	ldx #25
+delay_6_x:
+	dex
+	bne delay_6_x
+	dey
+	bne delay_6_y
+	lda $4015
+	and #$40
+	beq check_7
+	lda #6
+	jmp fail
+
+check_7:
+	lda #0
+	sta irq_count
+	sta $4017
+	cli
+	ldy #229		; 30000 cycles
+delay_7_y:
+	ldx #25
+delay_7_x:
+	dex
+	bne delay_7_x
+	dey
+	bne delay_7_y
+	sei
+	lda irq_count
+	cmp #1
+	beq done
+	lda #7
+	jmp fail
+
+done:
+	jmp pass
+
+	.org $FFFA
+	.dw nmi, reset, irq
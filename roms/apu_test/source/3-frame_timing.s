@@ -0,0 +1,106 @@
+; Timing of the frame counter after $4017 writes.
+;
+; A write takes effect 3 or 4 cycles later, then in 4-step mode the length is clocked
+; and the IRQ flag is set 29828-29830 cycles later, and in 5-step mode the length is
+; clocked at once and 14913 cycles later. $4015 is read a few cycles before and after.
+;
+; 2) The length is clocked too early in 4-step mode
+; 3) The length is clocked too late in 4-step mode
+; 4) The IRQ flag is set too early
+; 5) The IRQ flag is set too late
+; 6) The length is clocked too early in 5-step mode
+; 7) The length is clocked too late in 5-step mode
+
+	.include "shell.inc"
+
+name:
+	.db "3-frame_timing", 10, 10, 0
+
+main:
+	lda #$40		; 4-step mode, inhibited to clear the flag
+	sta $4017
+	lda #$01
+	sta $4015
+	lda #$10
+	sta $4000
+	lda #$18		; length 2
+	sta $4003
+	lda #$00
+	sta $4017		; written at W
+	ldy #37			; 29823 cycles
+delay_4_step_y:
+	ldx #160
+delay_4_step_x:
+	dex
+	bne delay_4_step_x
+	dey
+	bne delay_4_step_y
+	lda $4015		; read at W + 29827
+	sta early
+	nop
+	lda $4015		; read at W + 29836
+	sta late
+
+	lda early
+	and #$01
+	bne check_3
+	lda #2
+	jmp fail
+check_3:
+	lda late
+	and #$01
+	beq check_4
+	lda #3
+	jmp fail
+check_4:
+	lda early
+	and #$40
+	beq check_5
+	lda #4
+	jmp fail
+check_5:
+	lda late
+	and #$40
+	bne check_6
+	lda #5
+	jmp fail
+
+check_6:
+	lda #$C0		; 5-step mode, clocks the length while it's 0
+	sta $4017
+	lda #$18		; length 2
+	sta $4003
+	lda #$80
+	sta $4017		; written at W, clocks the length to 1
+	ldy #24			; 14907 cycles
+delay_5_step_y:
+	ldx #123
+delay_5_step_x:
+	dex
+	bne delay_5_step_x
+	dey
+	bne delay_5_step_y
+	nop
+	lda $4015		; read at W + 14911
+	sta early
+	nop
+	lda $4015		; read at W + 14920
+	sta late
+
+	lda early
+	and #$01
+	bne check_7
+	lda #6
+	jmp fail
+check_7:
+	lda late
+	and #$01
+	beq done
+	lda #7
+	jmp fail
+
+done:
+	jmp pass
+
+	.org $FFFA
+	.dw nmi, reset, irq
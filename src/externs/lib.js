@@ -2,45 +2,23 @@ mergeInto(LibraryManager.library, {
   canvas_render: function (ptr, len) {
     Module.NES.buf = new Uint8Array(Module.HEAPU8.buffer, ptr, len);
     Module.NES.image.data.set(Module.NES.buf);
-    Module.NES.ctx.putImageData(Module.NES.image, 0, 0);
+    const { overscan, canvas } = Module.NES;
+    Module.NES.ctx.putImageData(Module.NES.image, -overscan.left, -overscan.top,
+      overscan.left, overscan.top, canvas.width, canvas.height);
   },
-  start_oscillator: function (index) {
-    Module.NES.oscs[index].start();
+  audio_play: function (ptr, len) {
+    Module.NES.speaker.play(new Float32Array(Module.HEAPF32.buffer, ptr, len));
   },
-  stop_oscillator: function (index) {
-    Module.NES.oscs[index].stop();
+  audio_get_sample_rate: function () {
+    return Module.NES.speaker.sampleRate;
   },
-  close_oscillator: function (index) {
-    Module.NES.oscs[index].close();
+  audio_get_channel_gain: function (index) {
+    return Module.NES.mixer.gains[index];
   },
-  set_oscillator_frequency: function (index, freq) {
-    Module.NES.oscs[index].setFrequency(freq);
+  audio_is_channel_muted: function (index) {
+    return Module.NES.mixer.muted[index] ? 1 : 0;
   },
-  change_oscillator_frequency: function (index, freq) {
-    Module.NES.oscs[index].changeFrequency(freq);
-  },
-  set_oscillator_volume: function (index, volume) {
-    Module.NES.oscs[index].setVolume(volume);
-  },
-  set_oscillator_pulse_width: function (index, width) {
-    Module.NES.oscs[index].setPulseWidth(width);
-  },
-  set_noise_frequency: function (freq) {
-    Module.NES.noise.setFrequency(freq);
-  },
-  change_noise_frequency: function (freq) {
-    Module.NES.noise.changeFrequency(freq);
-  },  
-  set_noise_volume: function (volume) {
-    Module.NES.noise.setVolume(volume);
-  },
-  close_noise: function () {
-    Module.NES.noise.close();
-  },
-  stop_noise: function () {
-    Module.NES.noise.stop();
-  },
-  start_noise: function () {
-    Module.NES.noise.start();
+  audio_is_channel_soloed: function (index) {
+    return Module.NES.mixer.soloed[index] ? 1 : 0;
   }
 });
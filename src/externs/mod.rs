@@ -4,6 +4,8 @@ use std::ffi::CString;
 use std::ptr::null_mut;
 use std::os::raw::{c_int, c_void, c_uchar};
 
+use rustynes::nes::{MixerSettings, CHANNELS};
+
 #[allow(non_camel_case_types)]
 type em_callback_func = unsafe extern "C" fn();
 
@@ -24,6 +26,44 @@ extern "C" {
                                     simulate_infinite_loop: c_int);
 }
 
+extern "C" {
+    fn canvas_render(ptr: *const u8, len: usize);
+    fn audio_play(ptr: *const f32, len: usize);
+    fn audio_get_sample_rate() -> c_int;
+    fn audio_get_channel_gain(index: usize) -> f32;
+    fn audio_is_channel_muted(index: usize) -> c_int;
+    fn audio_is_channel_soloed(index: usize) -> c_int;
+}
+
+pub fn render(buf: &[u8]) {
+    unsafe {
+        canvas_render(buf.as_ptr(), buf.len());
+    }
+}
+
+pub fn play(samples: &[f32]) {
+    unsafe {
+        audio_play(samples.as_ptr(), samples.len());
+    }
+}
+
+pub fn get_sample_rate() -> u32 {
+    unsafe { audio_get_sample_rate() as u32 }
+}
+
+// Mixer settings changed from the page.
+pub fn get_mixer_settings() -> MixerSettings {
+    let mut settings = MixerSettings::new();
+    for (i, channel) in CHANNELS.iter().enumerate() {
+        unsafe {
+            settings.set_gain(*channel, audio_get_channel_gain(i));
+            settings.set_muted(*channel, audio_is_channel_muted(i) != 0);
+            settings.set_soloed(*channel, audio_is_channel_soloed(i) != 0);
+        }
+    }
+    settings
+}
+
 pub fn cancel_main_loop() {
     unsafe {
         emscripten_cancel_main_loop();
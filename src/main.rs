@@ -1,25 +1,55 @@
-#[macro_use]
-extern crate lazy_static;
-extern crate libc;
+extern crate rustynes;
 
-mod nes;
+#[cfg(target_os = "emscripten")]
 mod externs;
+#[cfg(not(target_os = "emscripten"))]
+mod runner;
 
-use nes::Context;
+#[cfg(target_os = "emscripten")]
+use rustynes::nes::{self, Context};
 
+#[cfg(not(target_os = "emscripten"))]
+fn main() {
+    std::process::exit(runner::run(std::env::args().skip(1).collect()));
+}
+
+#[cfg(target_os = "emscripten")]
 fn main() {}
 
+#[cfg(target_os = "emscripten")]
 #[no_mangle]
 pub fn run(len: usize, ptr: *mut u8) {
     let buf: &mut [u8] = unsafe { std::slice::from_raw_parts_mut(ptr, len + 1) };
-    let mut ctx = Context::new(buf);
+    let mut ctx = match Context::new(buf) {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    ctx.set_sample_rate(externs::get_sample_rate());
     nes::reset(&mut ctx);
     externs::cancel_main_loop();
+    let mut last_key_state = 0;
     let main_loop = || {
         let key_state = buf[len - 1];
+        // Left and right change the track of *.nsf files.
+        if let Some(count) = ctx.get_nsf().map(|nsf| nsf.track_count) {
+            let pressed = key_state & !last_key_state;
+            let track = ctx.get_track();
+            if pressed & 0x80 == 0x80 {
+                ctx.select_track((track + 1) % count);
+            } else if pressed & 0x40 == 0x40 {
+                ctx.select_track((track + count - 1) % count);
+            }
+        }
+        last_key_state = key_state;
         // externs::eval("console.time('nes.run')");
+        ctx.set_mixer_settings(externs::get_mixer_settings());
         nes::run(&mut ctx, key_state);
         // externs::eval("console.timeEnd('nes.run')");
+        externs::render(ctx.get_frame_buffer());
+        externs::play(ctx.get_audio_samples());
     };
     externs::set_main_loop_callback(main_loop);
 }
@@ -1,25 +1,24 @@
-
 pub const CPU_CLOCK: usize = 1789772;
 
-pub const GROBAL_GAIN: f32 = 0.1;
-
-pub const COUNTER_TABLE: &'static [u8] = &[0x0A, 0xFE, 0x14, 0x02, 0x28, 0x04, 0x50, 0x06, 0xA0,
-                                           0x08, 0x3C, 0x0A, 0x0E, 0x0C, 0x1A, 0x0E, 0x0C, 0x10,
-                                           0x18, 0x12, 0x30, 0x14, 0x60, 0x16, 0xC0, 0x18, 0x48,
-                                           0x1A, 0x10, 0x1C, 0x20, 0x1E];
+pub const COUNTER_TABLE: &[u8] = &[0x0A, 0xFE, 0x14, 0x02, 0x28, 0x04, 0x50, 0x06, 0xA0,
+                                   0x08, 0x3C, 0x0A, 0x0E, 0x0C, 0x1A, 0x0E, 0x0C, 0x10,
+                                   0x18, 0x12, 0x30, 0x14, 0x60, 0x16, 0xC0, 0x18, 0x48,
+                                   0x1A, 0x10, 0x1C, 0x20, 0x1E];
 
-pub const DIVIDE_COUNT_FOR_240HZ: u16 = 7457;
+// Waveform of each duty setting (12.5%, 25%, 50% and 25% negated), 8 steps per period.
+pub const DUTY_TABLE: &[[u8; 8]] = &[[0, 1, 0, 0, 0, 0, 0, 0],
+                                     [0, 1, 1, 0, 0, 0, 0, 0],
+                                     [0, 1, 1, 1, 1, 0, 0, 0],
+                                     [1, 0, 0, 1, 1, 1, 1, 1]];
 
+pub const TRIANGLE_SEQUENCE: &[u8] = &[15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+                                       0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
 
-pub const NOISE_TIMER_PERIOD_TABLE: &'static [u16] = &[0x004, 0x008, 0x010, 0x020, 0x040, 0x060,
-                                                      0x080, 0x0A0, 0x0CA, 0x0FE, 0x17C, 0x1FC,
-                                                      0x2FA, 0x3F8, 0x7F2, 0xFE4];
+// In CPU cycles.
+pub const NOISE_TIMER_PERIOD_TABLE: &[u16] = &[0x004, 0x008, 0x010, 0x020, 0x040, 0x060,
+                                               0x080, 0x0A0, 0x0CA, 0x0FE, 0x17C, 0x1FC,
+                                               0x2FA, 0x3F8, 0x7F2, 0xFE4];
 
-/*
-export const dmcTimerPeriodTable = [
-  0x1AC, 0x17C, 0x154, 0x140,
-  0x11E, 0x0FE, 0x0E2, 0x0D6,
-  0x0BE, 0x0A0, 0x08E, 0x080,
-  0x06A, 0x054, 0x048, 0x036,
-];
-*/
\ No newline at end of file
+// In CPU cycles.
+pub const DMC_TIMER_PERIOD_TABLE: &[u16] = &[0x1AC, 0x17C, 0x154, 0x140, 0x11E, 0x0FE, 0x0E2, 0x0D6,
+                                             0x0BE, 0x0A0, 0x08E, 0x080, 0x06A, 0x054, 0x048, 0x036];
@@ -0,0 +1,276 @@
+use super::constants::DMC_TIMER_PERIOD_TABLE;
+use nes::mapper::Mapper;
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::{Data, Addr};
+
+// The memory reader halts the CPU for this many cycles to fetch a sample byte.
+const DMA_STALL_CYCLES: u16 = 4;
+
+// Delta modulation channel ($4010-$4013)
+//
+// The memory reader fetches 1 bit delta samples from $C000-$FFFF into the sample buffer,
+// and the output unit shifts them out on each timer clock, raising or lowering
+// the 7bit output level by 2. A new byte is taken from the buffer every 8 bits.
+//
+// | addr  | bits      | description                                  |
+// |-------|-----------|----------------------------------------------|
+// | $4010 | IL-- RRRR | irq enable, loop, rate index                 |
+// | $4011 | -DDD DDDD | direct load of the output level              |
+// | $4012 | AAAA AAAA | sample address = $C000 + A * 64              |
+// | $4013 | LLLL LLLL | sample length = L * 16 + 1 bytes             |
+#[derive(Debug)]
+pub struct Dmc {
+    is_irq_enabled: bool,
+    is_loop: bool,
+    timer_period: u16,
+    timer: u16,
+    level: Data,
+    sample_addr: Addr,
+    sample_length: u16,
+    // Memory reader
+    current_addr: Addr,
+    bytes_remaining: u16,
+    sample_buffer: Option<Data>,
+    // Output unit
+    shift_register: Data,
+    bits_remaining: u8,
+    is_silenced: bool,
+    is_irq_asserted: bool,
+    stall_cycles: u16,
+}
+
+impl Dmc {
+    pub fn new() -> Self {
+        Dmc {
+            is_irq_enabled: false,
+            is_loop: false,
+            timer_period: DMC_TIMER_PERIOD_TABLE[0],
+            timer: 0,
+            level: 0,
+            sample_addr: 0xC000,
+            sample_length: 1,
+            current_addr: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            is_silenced: true,
+            is_irq_asserted: false,
+            stall_cycles: 0,
+        }
+    }
+
+    // Bit 4 of $4015, which also acknowledges the IRQ.
+    pub fn set_enabled(&mut self, is_enabled: bool) {
+        self.is_irq_asserted = false;
+        if !is_enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    pub fn is_irq_asserted(&self) -> bool {
+        self.is_irq_asserted
+    }
+
+    // CPU cycles stolen by the memory reader since the last call.
+    pub fn take_stall_cycles(&mut self) -> u16 {
+        let cycles = self.stall_cycles;
+        self.stall_cycles = 0;
+        cycles
+    }
+
+    pub fn clock_timer(&mut self, mapper: &dyn Mapper) {
+        self.fill_sample_buffer(mapper);
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            self.clock_output();
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    // 0-127
+    pub fn get_output(&self) -> Data {
+        self.level
+    }
+
+    pub fn write(&mut self, addr: Addr, data: Data) {
+        match addr {
+            0x00 => {
+                self.is_irq_enabled = data & 0x80 == 0x80;
+                self.is_loop = data & 0x40 == 0x40;
+                self.timer_period = DMC_TIMER_PERIOD_TABLE[data as usize & 0xF];
+                if !self.is_irq_enabled {
+                    self.is_irq_asserted = false;
+                }
+            }
+            0x01 => self.level = data & 0x7F,
+            0x02 => self.sample_addr = 0xC000 | (data as Addr) << 6,
+            0x03 => self.sample_length = (data as u16) << 4 | 1,
+            _ => (),
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_addr = self.sample_addr;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    fn fill_sample_buffer(&mut self, mapper: &dyn Mapper) {
+        if self.sample_buffer.is_some() || self.bytes_remaining == 0 {
+            return;
+        }
+        self.stall_cycles += DMA_STALL_CYCLES;
+        self.sample_buffer = Some(mapper.read(self.current_addr));
+        // Wraps around to $8000 after $FFFF.
+        self.current_addr = self.current_addr.wrapping_add(1) | 0x8000;
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.is_loop {
+                self.restart();
+            } else if self.is_irq_enabled {
+                self.is_irq_asserted = true;
+            }
+        }
+    }
+
+    fn clock_output(&mut self) {
+        if !self.is_silenced {
+            if self.shift_register & 0x01 == 0x01 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(data) => {
+                    self.shift_register = data;
+                    self.is_silenced = false;
+                }
+                None => self.is_silenced = true,
+            }
+        }
+    }
+}
+
+impl Default for Dmc {
+    fn default() -> Self {
+        Dmc::new()
+    }
+}
+
+impl Snapshot for Dmc {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.is_irq_enabled);
+        writer.write_bool(self.is_loop);
+        writer.write_u16(self.timer_period);
+        writer.write_u16(self.timer);
+        writer.write_u8(self.level);
+        writer.write_u16(self.sample_addr);
+        writer.write_u16(self.sample_length);
+        writer.write_u16(self.current_addr);
+        writer.write_u16(self.bytes_remaining);
+        writer.write_bool(self.sample_buffer.is_some());
+        writer.write_u8(self.sample_buffer.unwrap_or(0));
+        writer.write_u8(self.shift_register);
+        writer.write_u8(self.bits_remaining);
+        writer.write_bool(self.is_silenced);
+        writer.write_bool(self.is_irq_asserted);
+        writer.write_u16(self.stall_cycles);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.is_irq_enabled = reader.read_bool();
+        self.is_loop = reader.read_bool();
+        self.timer_period = reader.read_u16();
+        self.timer = reader.read_u16();
+        self.level = reader.read_u8();
+        self.sample_addr = reader.read_u16();
+        self.sample_length = reader.read_u16();
+        self.current_addr = reader.read_u16();
+        self.bytes_remaining = reader.read_u16();
+        let has_sample = reader.read_bool();
+        let sample = reader.read_u8();
+        self.sample_buffer = if has_sample { Some(sample) } else { None };
+        self.shift_register = reader.read_u8();
+        self.bits_remaining = reader.read_u8();
+        self.is_silenced = reader.read_bool();
+        self.is_irq_asserted = reader.read_bool();
+        self.stall_cycles = reader.read_u16();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nes::mapper::{create_mapper, Mirroring};
+    use nes::parser::Cassette;
+
+    // Program rom filled with 0xFF, so every delta raises the level.
+    fn create_mapper_with_samples() -> Box<dyn Mapper> {
+        create_mapper(Cassette {
+            mirroring: Mirroring::Vertical,
+            character_ram: vec![],
+            program_rom: vec![0xFF; 0x8000],
+            mapper: 0,
+            program_ram_size: 0x2000,
+            has_battery: false,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_play_sample_and_assert_irq() {
+        let mapper = create_mapper_with_samples();
+        let mut dmc = Dmc::new();
+        // IRQ enabled, fastest rate of 54 cycles, 17 bytes.
+        dmc.write(0x00, 0x8F);
+        dmc.write(0x03, 0x01);
+        dmc.set_enabled(true);
+        assert!(dmc.is_active());
+        for _ in 0..54 * 8 * 17 {
+            dmc.clock_timer(&*mapper);
+        }
+        assert!(!dmc.is_active());
+        assert!(dmc.is_irq_asserted());
+        assert_eq!(dmc.take_stall_cycles(), DMA_STALL_CYCLES * 17);
+        assert_eq!(dmc.take_stall_cycles(), 0);
+        // Clamped at 127 and 126 after rising by 2 for every bit.
+        assert!(dmc.get_output() >= 126);
+        // Enabling again acknowledges the IRQ and restarts the sample.
+        dmc.set_enabled(true);
+        assert!(!dmc.is_irq_asserted());
+        assert!(dmc.is_active());
+    }
+
+    #[test]
+    fn test_loop_sample() {
+        let mapper = create_mapper_with_samples();
+        let mut dmc = Dmc::new();
+        dmc.write(0x00, 0xCF);
+        dmc.write(0x01, 0x10);
+        dmc.write(0x02, 0xFF);
+        dmc.set_enabled(true);
+        assert_eq!(dmc.get_output(), 0x10);
+        for _ in 0..54 * 8 * 4 {
+            dmc.clock_timer(&*mapper);
+        }
+        // Looping samples never end nor raise the IRQ.
+        assert!(dmc.is_active());
+        assert!(!dmc.is_irq_asserted());
+        dmc.set_enabled(false);
+        assert!(!dmc.is_active());
+    }
+}
@@ -0,0 +1,110 @@
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::Data;
+
+// Volume of the square and noise channels, clocked by the quarter frame.
+// Decays from 15 to 0 at the rate of the lower 4 bits, or outputs them as a constant volume.
+//
+// | bit | description                             |
+// +-----+-----------------------------------------+
+// | 5   | loop, shared with the length halt flag  |
+// | 4   | constant volume                         |
+// | 3-0 | volume or decay period                  |
+#[derive(Debug)]
+pub struct Envelope {
+    is_start: bool,
+    is_loop: bool,
+    is_constant: bool,
+    volume: Data,
+    divider: Data,
+    decay: Data,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Envelope {
+            is_start: false,
+            is_loop: false,
+            is_constant: false,
+            volume: 0,
+            divider: 0,
+            decay: 0,
+        }
+    }
+
+    pub fn write(&mut self, data: Data) {
+        self.is_loop = data & 0x20 == 0x20;
+        self.is_constant = data & 0x10 == 0x10;
+        self.volume = data & 0x0F;
+    }
+
+    // Writing the 4th register of the channel restarts the decay on the next clock.
+    pub fn restart(&mut self) {
+        self.is_start = true;
+    }
+
+    pub fn clock(&mut self) {
+        if self.is_start {
+            self.is_start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.is_loop {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn get_volume(&self) -> Data {
+        if self.is_constant {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope::new()
+    }
+}
+
+impl Snapshot for Envelope {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.is_start);
+        writer.write_bool(self.is_loop);
+        writer.write_bool(self.is_constant);
+        writer.write_u8(self.volume);
+        writer.write_u8(self.divider);
+        writer.write_u8(self.decay);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.is_start = reader.read_bool();
+        self.is_loop = reader.read_bool();
+        self.is_constant = reader.read_bool();
+        self.volume = reader.read_u8();
+        self.divider = reader.read_u8();
+        self.decay = reader.read_u8();
+    }
+}
+
+#[test]
+fn test_envelope_decay() {
+    let mut envelope = Envelope::new();
+    envelope.write(0x01);
+    envelope.restart();
+    envelope.clock();
+    assert_eq!(envelope.get_volume(), 15);
+    // The divider counts 1, 0 before each step.
+    envelope.clock();
+    envelope.clock();
+    assert_eq!(envelope.get_volume(), 14);
+    envelope.write(0x17);
+    assert_eq!(envelope.get_volume(), 7);
+}
@@ -0,0 +1,86 @@
+use super::constants::COUNTER_TABLE;
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::Data;
+
+// Silences the channel after the time loaded by the 4th register, clocked by the half frame.
+// Disabling the channel with $4015 clears it and prevents loading until enabled again.
+#[derive(Debug)]
+pub struct LengthCounter {
+    counter: usize,
+    is_halted: bool,
+    is_enabled: bool,
+}
+
+impl LengthCounter {
+    pub fn new() -> Self {
+        LengthCounter {
+            counter: 0,
+            is_halted: false,
+            is_enabled: false,
+        }
+    }
+
+    pub fn set_enabled(&mut self, is_enabled: bool) {
+        self.is_enabled = is_enabled;
+        if !is_enabled {
+            self.counter = 0;
+        }
+    }
+
+    pub fn set_halted(&mut self, is_halted: bool) {
+        self.is_halted = is_halted;
+    }
+
+    // Bit 7-3 of the 4th register.
+    pub fn write(&mut self, data: Data) {
+        if self.is_enabled {
+            self.counter = COUNTER_TABLE[(data >> 3) as usize] as usize;
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if !self.is_halted && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.counter > 0
+    }
+}
+
+impl Default for LengthCounter {
+    fn default() -> Self {
+        LengthCounter::new()
+    }
+}
+
+impl Snapshot for LengthCounter {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_usize(self.counter);
+        writer.write_bool(self.is_halted);
+        writer.write_bool(self.is_enabled);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.counter = reader.read_usize();
+        self.is_halted = reader.read_bool();
+        self.is_enabled = reader.read_bool();
+    }
+}
+
+#[test]
+fn test_length_counter() {
+    let mut counter = LengthCounter::new();
+    counter.write(0x08);
+    assert!(!counter.is_active());
+    counter.set_enabled(true);
+    // Index 1 is 254 half frames.
+    counter.write(0x08);
+    for _ in 0..253 {
+        counter.clock();
+    }
+    assert!(counter.is_active());
+    counter.clock();
+    assert!(!counter.is_active());
+}
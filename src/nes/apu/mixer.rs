@@ -0,0 +1,211 @@
+use nes::types::Data;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Square1,
+    Square2,
+    Triangle,
+    Noise,
+    Dmc,
+}
+
+pub const CHANNELS: [Channel; 5] = [Channel::Square1,
+                                    Channel::Square2,
+                                    Channel::Triangle,
+                                    Channel::Noise,
+                                    Channel::Dmc];
+
+impl Channel {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Channel::Square1 => "square1",
+            Channel::Square2 => "square2",
+            Channel::Triangle => "triangle",
+            Channel::Noise => "noise",
+            Channel::Dmc => "dmc",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Channel> {
+        CHANNELS.iter().cloned().find(|c| c.get_name() == name)
+    }
+}
+
+// Listening controls of each channel, applied to the levels before mixing.
+// They are not part of the emulated machine, so the registers and the save states are unaffected.
+// While any channel is soloed, only the soloed channels are heard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixerSettings {
+    gains: [f32; 5],
+    is_muted: [bool; 5],
+    is_soloed: [bool; 5],
+}
+
+impl MixerSettings {
+    pub fn new() -> Self {
+        MixerSettings {
+            gains: [1.0; 5],
+            is_muted: [false; 5],
+            is_soloed: [false; 5],
+        }
+    }
+
+    pub fn set_gain(&mut self, channel: Channel, gain: f32) {
+        self.gains[channel as usize] = gain.max(0.0);
+    }
+
+    pub fn get_gain(&self, channel: Channel) -> f32 {
+        self.gains[channel as usize]
+    }
+
+    pub fn set_muted(&mut self, channel: Channel, is_muted: bool) {
+        self.is_muted[channel as usize] = is_muted;
+    }
+
+    pub fn is_muted(&self, channel: Channel) -> bool {
+        self.is_muted[channel as usize]
+    }
+
+    pub fn set_soloed(&mut self, channel: Channel, is_soloed: bool) {
+        self.is_soloed[channel as usize] = is_soloed;
+    }
+
+    pub fn is_soloed(&self, channel: Channel) -> bool {
+        self.is_soloed[channel as usize]
+    }
+
+    // Gain after muting and soloing.
+    pub fn get_effective_gain(&self, channel: Channel) -> f32 {
+        let has_solo = self.is_soloed.iter().any(|s| *s);
+        if self.is_muted(channel) || (has_solo && !self.is_soloed(channel)) {
+            0.0
+        } else {
+            self.get_gain(channel)
+        }
+    }
+}
+
+impl Default for MixerSettings {
+    fn default() -> Self {
+        MixerSettings::new()
+    }
+}
+
+// Nonlinear DAC of the 2A03, approximated with the lookup tables from nesdev.
+//
+// square_out = 95.52 / (8128 / (square1 + square2) + 100)
+// tnd_out    = 163.67 / (24329 / (3 * triangle + 2 * noise + dmc) + 100)
+//
+// The output is 0.0-1.0.
+// The tables only cover integer levels, so the formulas are used while the settings scale any level.
+#[derive(Debug)]
+pub struct Mixer {
+    square_table: Vec<f32>,
+    tnd_table: Vec<f32>,
+    settings: MixerSettings,
+    is_default_settings: bool,
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        Mixer {
+            square_table: (0..31).map(|n| square_out(n as f32)).collect(),
+            tnd_table: (0..203).map(|n| tnd_out(n as f32)).collect(),
+            settings: MixerSettings::new(),
+            is_default_settings: true,
+        }
+    }
+
+    pub fn get_settings(&self) -> &MixerSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: MixerSettings) {
+        self.is_default_settings = settings == MixerSettings::new();
+        self.settings = settings;
+    }
+
+    // Outputs of the channels, 0-15 except for the dmc of 0-127.
+    pub fn mix(&self, square1: Data, square2: Data, triangle: Data, noise: Data, dmc: Data) -> f32 {
+        if self.is_default_settings {
+            let square = self.square_table[(square1 + square2) as usize];
+            let tnd = self.tnd_table[3 * triangle as usize + 2 * noise as usize + dmc as usize];
+            return square + tnd;
+        }
+        let level = |channel: Channel, output: Data| self.settings.get_effective_gain(channel) * output as f32;
+        let square = level(Channel::Square1, square1) + level(Channel::Square2, square2);
+        let tnd = 3.0 * level(Channel::Triangle, triangle) + 2.0 * level(Channel::Noise, noise) +
+                  level(Channel::Dmc, dmc);
+        square_out(square) + tnd_out(tnd)
+    }
+
+    // Level of a channel played alone, the settings are not applied.
+    pub fn mix_channel(&self, channel: Channel, output: Data) -> f32 {
+        match channel {
+            Channel::Square1 | Channel::Square2 => self.square_table[output as usize],
+            Channel::Triangle => self.tnd_table[3 * output as usize],
+            Channel::Noise => self.tnd_table[2 * output as usize],
+            Channel::Dmc => self.tnd_table[output as usize],
+        }
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Mixer::new()
+    }
+}
+
+fn square_out(n: f32) -> f32 {
+    if n <= 0.0 { 0.0 } else { 95.52 / (8128.0 / n + 100.0) }
+}
+
+fn tnd_out(n: f32) -> f32 {
+    if n <= 0.0 { 0.0 } else { 163.67 / (24329.0 / n + 100.0) }
+}
+
+#[test]
+fn test_mix() {
+    let mixer = Mixer::new();
+    assert_eq!(mixer.mix(0, 0, 0, 0, 0), 0.0);
+    let max = mixer.mix(15, 15, 15, 15, 127);
+    assert!(max > 0.99 && max < 1.01);
+    // Two squares are quieter than twice of one.
+    assert!(mixer.mix(15, 15, 0, 0, 0) < mixer.mix(15, 0, 0, 0, 0) * 2.0);
+}
+
+#[test]
+fn test_mix_channel() {
+    let mixer = Mixer::new();
+    assert_eq!(mixer.mix_channel(Channel::Square2, 15), mixer.mix(0, 15, 0, 0, 0));
+    assert_eq!(mixer.mix_channel(Channel::Noise, 15), mixer.mix(0, 0, 0, 15, 0));
+    assert_eq!(mixer.mix_channel(Channel::Dmc, 127), mixer.mix(0, 0, 0, 0, 127));
+}
+
+#[test]
+fn test_mute_and_solo() {
+    let mut mixer = Mixer::new();
+    let square1 = mixer.mix(15, 0, 0, 0, 0);
+    let triangle = mixer.mix(0, 0, 15, 0, 0);
+    let mut settings = MixerSettings::new();
+    settings.set_muted(Channel::Square2, true);
+    settings.set_muted(Channel::Noise, true);
+    mixer.set_settings(settings.clone());
+    assert!((mixer.mix(15, 15, 15, 15, 0) - (square1 + triangle)).abs() < 1e-6);
+    // Solo wins over the other channels, but not over the mute.
+    settings.set_soloed(Channel::Triangle, true);
+    settings.set_soloed(Channel::Noise, true);
+    mixer.set_settings(settings.clone());
+    assert!((mixer.mix(15, 15, 15, 15, 127) - triangle).abs() < 1e-6);
+    settings.set_gain(Channel::Triangle, 0.5);
+    mixer.set_settings(settings);
+    assert!(mixer.mix(15, 15, 15, 15, 127) < triangle);
+}
+
+#[test]
+fn test_channel_name() {
+    for channel in CHANNELS.iter() {
+        assert_eq!(Channel::from_name(channel.get_name()), Some(*channel));
+    }
+    assert_eq!(Channel::from_name("pulse"), None);
+}
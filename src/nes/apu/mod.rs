@@ -1,55 +1,188 @@
 mod constants;
+mod dmc;
+mod envelope;
+mod length_counter;
+mod mixer;
 mod noise;
+mod resampler;
 mod square;
 mod triangle;
 
 use self::constants::*;
+use self::dmc::Dmc;
+use self::mixer::Mixer;
+pub use self::mixer::{Channel, MixerSettings, CHANNELS};
 use self::noise::Noise;
+use self::resampler::Resampler;
 use self::square::Square;
 use self::triangle::Triangle;
+use nes::interrupts::{Interrupts, IrqSource};
+use nes::mapper::Mapper;
+use nes::state::{Snapshot, StateReader, StateWriter};
 use nes::types::{Addr, Data};
 
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
 #[derive(Debug)]
 pub struct Apu {
     squares: (Square, Square),
     triangle: Triangle,
     noise: Noise,
-    cycle: u16,
-    step: usize,
-    sequencer_mode: bool,
-    enable_irq: bool,
+    dmc: Dmc,
+    // CPU cycles since the frame counter was reset.
+    frame_cycle: u32,
+    is_five_step_mode: bool,
+    is_irq_inhibited: bool,
+    is_frame_irq_asserted: bool,
+    // Registers written by the CPU during the last instruction, applied once its cycles are run.
+    cpu_writes: Vec<(Addr, Data)>,
+    // $4017 written by the last instruction, scheduled once the write cycle is reached.
+    frame_counter_write: Option<Data>,
+    // The written mode takes effect after the delay of 3 or 4 cycles.
+    pending_frame_counter: Option<(Data, u8)>,
+    // The square and noise timers are clocked on odd CPU cycles.
+    is_odd_cycle: bool,
+    mixer: Mixer,
+    resampler: Resampler,
+    // A resampler for each channel played alone while the stems are enabled.
+    stems: Vec<Resampler>,
 }
 
 impl Apu {
     pub fn new() -> Self {
         Apu {
             squares: (Square::new(0), Square::new(1)),
-            triangle: Triangle::new(2),
+            triangle: Triangle::new(),
             noise: Noise::new(),
-            cycle: 0,
-            step: 0,
-            sequencer_mode: false,
-            enable_irq: false,
+            dmc: Dmc::new(),
+            frame_cycle: 0,
+            is_five_step_mode: false,
+            is_irq_inhibited: false,
+            is_frame_irq_asserted: false,
+            cpu_writes: Vec::new(),
+            frame_counter_write: None,
+            pending_frame_counter: None,
+            is_odd_cycle: false,
+            mixer: Mixer::new(),
+            resampler: Resampler::new(CPU_CLOCK as f64, DEFAULT_SAMPLE_RATE),
+            stems: Vec::new(),
         }
     }
 
-    pub fn run(&mut self, cycle: u16) {
-        self.cycle += cycle;
-        if self.cycle >= DIVIDE_COUNT_FOR_240HZ {
-            // invoked by 240hz
-            self.cycle -= DIVIDE_COUNT_FOR_240HZ;
-            if self.sequencer_mode {
-                self.update_by_sequence_mode1();
-            } else {
-                self.update_by_sequence_mode0();
-            }
+    // The DMC reads its samples from the cartridge.
+    pub fn run(&mut self, cycle: u16, interrupts: &mut Interrupts, mapper: &dyn Mapper) {
+        for _ in 0..cycle {
+            self.step(mapper);
+        }
+        for (addr, data) in std::mem::take(&mut self.cpu_writes) {
+            self.write(addr, data);
+        }
+        // Writes are on the last cycle of the instruction, which has just been run.
+        if let Some(data) = self.frame_counter_write.take() {
+            // 3 cycles when written on an APU cycle, the odd one, otherwise 4.
+            let delay = if self.is_odd_cycle { 4 } else { 3 };
+            self.pending_frame_counter = Some((data, delay));
+        }
+        if self.is_frame_irq_asserted {
+            interrupts.assert_irq(IrqSource::FrameCounter);
+        } else {
+            interrupts.deassert_irq(IrqSource::FrameCounter);
+        }
+        if self.dmc.is_irq_asserted() {
+            interrupts.assert_irq(IrqSource::Dmc);
+        } else {
+            interrupts.deassert_irq(IrqSource::Dmc);
+        }
+    }
+
+    // CPU cycles stolen by the DMC memory reader, the CPU is halted for them.
+    pub fn take_stall_cycles(&mut self) -> Option<u16> {
+        match self.dmc.take_stall_cycles() {
+            0 => None,
+            cycles => Some(cycles),
+        }
+    }
+
+    // Samples generated since the last `clear_samples`, -1.0 to 1.0 at the sample rate.
+    pub fn get_samples(&self) -> &[f32] {
+        self.resampler.get_samples()
+    }
+
+    pub fn clear_samples(&mut self) {
+        self.resampler.clear_samples();
+        for stem in &mut self.stems {
+            stem.clear_samples();
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.resampler.set_sample_rate(sample_rate);
+        for stem in &mut self.stems {
+            stem.set_sample_rate(sample_rate);
+        }
+    }
+
+    pub fn set_stems_enabled(&mut self, is_enabled: bool) {
+        self.stems = if is_enabled {
+            CHANNELS
+                .iter()
+                .map(|_| Resampler::new(CPU_CLOCK as f64, self.get_sample_rate()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+    }
+
+    // Samples of the channel alone, empty while the stems are disabled.
+    pub fn get_stem_samples(&self, channel: Channel) -> &[f32] {
+        self.stems
+            .get(channel as usize)
+            .map_or(&[], |stem| stem.get_samples())
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.resampler.get_sample_rate()
+    }
+
+    pub fn get_mixer_settings(&self) -> &MixerSettings {
+        self.mixer.get_settings()
+    }
+
+    pub fn set_mixer_settings(&mut self, settings: MixerSettings) {
+        self.mixer.set_settings(settings);
+    }
+
+    fn step(&mut self, mapper: &dyn Mapper) {
+        self.step_frame_counter();
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer(mapper);
+        if self.is_odd_cycle {
+            self.squares.0.clock_timer();
+            self.squares.1.clock_timer();
+        }
+        self.is_odd_cycle = !self.is_odd_cycle;
+        let outputs = [
+            self.squares.0.get_output(),
+            self.squares.1.get_output(),
+            self.triangle.get_output(),
+            self.noise.get_output(),
+            self.dmc.get_output(),
+        ];
+        let level = self.mixer.mix(outputs[0], outputs[1], outputs[2], outputs[3], outputs[4]);
+        self.resampler.clock(level);
+        for (i, stem) in self.stems.iter_mut().enumerate() {
+            stem.clock(self.mixer.mix_channel(CHANNELS[i], outputs[i]));
         }
     }
 
     pub fn read(&mut self, addr: Addr) -> Data {
         match addr {
             0x15 => {
-                // self.interrupts.deassertIrq();
+                let i = if self.dmc.is_irq_asserted() { 0x80 } else { 0x00 };
+                let f = if self.is_frame_irq_asserted { 0x40 } else { 0x00 };
+                self.is_frame_irq_asserted = false;
+                let d = if self.dmc.is_active() { 0x10 } else { 0x00 };
                 let s0 = if self.squares.0.has_count_end() {
                     0x00
                 } else {
@@ -70,96 +203,243 @@ impl Apu {
                 } else {
                     0x08
                 };
-                n | t | s1 | s0
+                i | f | d | n | t | s1 | s0
             }
             _ => 0,
         }
     }
 
+    // The CPU writes on the last cycle of the instruction, so the write is kept until `run` has
+    // caught up with it. Otherwise a register could change before an earlier $4017 write takes effect.
+    pub fn write_from_cpu(&mut self, addr: Addr, data: Data) {
+        self.cpu_writes.push((addr, data));
+    }
+
     pub fn write(&mut self, addr: Addr, data: Data) {
         match addr {
-            0x00...0x03 => {
-                self.squares.0.write(addr, data);
-            }
-            0x04...0x07 => {
-                self.squares.1.write(addr - 0x04, data);
-            }
-            0x08...0x0b => {
-                self.triangle.write(addr - 0x08, data);
-            }
-            0x0c...0x0f => {
-                self.noise.write(addr - 0x0c, data);
-            }
+            0x00..=0x03 => self.squares.0.write(addr, data),
+            0x04..=0x07 => self.squares.1.write(addr - 0x04, data),
+            0x08..=0x0b => self.triangle.write(addr - 0x08, data),
+            0x0c..=0x0f => self.noise.write(addr - 0x0c, data),
+            0x10..=0x13 => self.dmc.write(addr - 0x10, data),
             0x15 => {
-                if data & 0x01 == 0x01 {
-                    self.squares.0.enable();
-                } else {
-                    self.squares.0.disable();
-                }
-                if data & 0x02 == 0x02 {
-                    self.squares.1.enable();
-                } else {
-                    self.squares.1.disable();
-                }
-                if data & 0x04 == 0x04 {
-                    self.triangle.enable();
-                } else {
-                    self.triangle.disable();
-                }
-                if data & 0x08 == 0x08 {
-                    self.noise.start();
-                } else {
-                    self.noise.stop();
-                }
+                self.squares.0.set_enabled(data & 0x01 == 0x01);
+                self.squares.1.set_enabled(data & 0x02 == 0x02);
+                self.triangle.set_enabled(data & 0x04 == 0x04);
+                self.noise.set_enabled(data & 0x08 == 0x08);
+                self.dmc.set_enabled(data & 0x10 == 0x10);
             }
             0x17 => {
-                self.sequencer_mode = data & 0x80 == 0x80;
-                self.enable_irq = data & 0x40 == 0x40;
-                // if self.sequencer_mode {
-                self.step = 0;
-                self.cycle = 0;
-                // }
+                // bit6 is the IRQ inhibit flag, which takes effect immediately.
+                self.is_irq_inhibited = data & 0x40 == 0x40;
+                if self.is_irq_inhibited {
+                    self.is_frame_irq_asserted = false;
+                }
+                self.frame_counter_write = Some(data);
             }
             _ => (), //println!("addr {} data {}", addr, data),
         }
     }
 
-    fn update_by_sequence_mode0(&mut self) {
-        self.update_envelope();
-        if self.step % 2 == 1 {
-            self.update_counters();
+    // Frame sequencer in CPU cycles after the reset by $4017.
+    //
+    // | 4 step  | 5 step  | clock                      |
+    // |---------|---------|----------------------------|
+    // | 7457    | 7457    | quarter                    |
+    // | 14913   | 14913   | quarter, half              |
+    // | 22371   | 22371   | quarter                    |
+    // | 29828   | -       | IRQ                        |
+    // | 29829   | -       | quarter, half, IRQ         |
+    // | 29830=0 | -       | IRQ                        |
+    // | -       | 37281   | quarter, half              |
+    // | -       | 37282=0 |                            |
+    //
+    // The IRQ flag is set unless inhibited and kept until read from $4015.
+    fn step_frame_counter(&mut self) {
+        if let Some((data, delay)) = self.pending_frame_counter {
+            if delay > 1 {
+                self.pending_frame_counter = Some((data, delay - 1));
+            } else {
+                self.pending_frame_counter = None;
+                self.frame_cycle = 0;
+                self.is_five_step_mode = data & 0x80 == 0x80;
+                // 5 step mode clocks all units as soon as it is written.
+                if self.is_five_step_mode {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+            }
         }
-        self.step += 1;
-        if self.step == 4 {
-            if self.enable_irq {
-                //self.interrupts.assert_irq();
+        self.frame_cycle += 1;
+        match (self.is_five_step_mode, self.frame_cycle) {
+            (_, 7457) | (_, 22371) => self.clock_quarter_frame(),
+            (_, 14913) | (false, 29829) | (true, 37281) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
             }
-            self.step = 0;
+            _ => (),
         }
-    }
-
-    fn update_by_sequence_mode1(&mut self) {
-        if self.step % 2 == 0 {
-            self.update_counters();
+        if !self.is_five_step_mode && self.frame_cycle >= 29828 && !self.is_irq_inhibited {
+            self.is_frame_irq_asserted = true;
         }
-        self.step += 1;
-        if self.step == 5 {
-            self.step = 0;
-        } else {
-            self.update_envelope();
+        match (self.is_five_step_mode, self.frame_cycle) {
+            (false, 29830) | (true, 37282) => self.frame_cycle = 0,
+            _ => (),
         }
     }
 
-    fn update_counters(&mut self) {
-        self.squares.0.update_counters();
-        self.squares.1.update_counters();
-        self.triangle.update_counter();
-        self.noise.update_counter();
+    // Envelopes and the linear counter.
+    fn clock_quarter_frame(&mut self) {
+        self.squares.0.clock_quarter_frame();
+        self.squares.1.clock_quarter_frame();
+        self.triangle.clock_quarter_frame();
+        self.noise.clock_quarter_frame();
+    }
+
+    // Length counters and sweeps.
+    fn clock_half_frame(&mut self) {
+        self.squares.0.clock_half_frame();
+        self.squares.1.clock_half_frame();
+        self.triangle.clock_half_frame();
+        self.noise.clock_half_frame();
+    }
+}
+
+// Generated samples are not part of the state, they are flushed every frame.
+impl Snapshot for Apu {
+    fn save(&self, writer: &mut StateWriter) {
+        self.squares.0.save(writer);
+        self.squares.1.save(writer);
+        self.triangle.save(writer);
+        self.noise.save(writer);
+        self.dmc.save(writer);
+        writer.write_u32(self.frame_cycle);
+        writer.write_bool(self.is_five_step_mode);
+        writer.write_bool(self.is_irq_inhibited);
+        writer.write_bool(self.is_frame_irq_asserted);
+        // Instructions are not interrupted by a save, so a write is never left unscheduled.
+        writer.write_bool(self.pending_frame_counter.is_some());
+        let (data, delay) = self.pending_frame_counter.unwrap_or((0, 0));
+        writer.write_u8(data);
+        writer.write_u8(delay);
+        writer.write_bool(self.is_odd_cycle);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.squares.0.load(reader);
+        self.squares.1.load(reader);
+        self.triangle.load(reader);
+        self.noise.load(reader);
+        self.dmc.load(reader);
+        self.frame_cycle = reader.read_u32();
+        self.is_five_step_mode = reader.read_bool();
+        self.is_irq_inhibited = reader.read_bool();
+        self.is_frame_irq_asserted = reader.read_bool();
+        let is_pending = reader.read_bool();
+        let data = reader.read_u8();
+        let delay = reader.read_u8();
+        self.pending_frame_counter = if is_pending { Some((data, delay)) } else { None };
+        self.frame_counter_write = None;
+        self.is_odd_cycle = reader.read_bool();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nes::mapper::{create_mapper, Mirroring};
+    use nes::parser::Cassette;
+
+    fn create_mapper_for_test() -> Box<dyn Mapper> {
+        create_mapper(Cassette {
+            mirroring: Mirroring::Vertical,
+            character_ram: vec![],
+            program_rom: vec![0; 0x8000],
+            mapper: 0,
+            program_ram_size: 0x2000,
+            has_battery: false,
+        })
+        .unwrap()
+    }
+
+    // Writes $4017 by an instruction of 4 cycles and waits for it to take effect.
+    fn write_frame_counter(apu: &mut Apu, data: Data, mapper: &dyn Mapper) {
+        let mut interrupts = Interrupts::new();
+        apu.write(0x17, data);
+        apu.run(4, &mut interrupts, mapper);
+        apu.run(4, &mut interrupts, mapper);
+    }
+
+    #[test]
+    fn test_frame_irq() {
+        let mapper = create_mapper_for_test();
+        let mut apu = Apu::new();
+        let mut interrupts = Interrupts::new();
+        write_frame_counter(&mut apu, 0x00, &*mapper);
+        apu.run(29820, &mut interrupts, &*mapper);
+        assert_eq!(apu.read(0x15) & 0x40, 0x00);
+        apu.run(20, &mut interrupts, &*mapper);
+        assert!(interrupts.is_irq_asserted_by(IrqSource::FrameCounter));
+        // Cleared by the read.
+        assert_eq!(apu.read(0x15) & 0x40, 0x40);
+        assert_eq!(apu.read(0x15) & 0x40, 0x00);
+        apu.run(1, &mut interrupts, &*mapper);
+        assert!(!interrupts.is_irq_asserted_by(IrqSource::FrameCounter));
+        // Next frame
+        apu.run(29830, &mut interrupts, &*mapper);
+        assert_eq!(apu.read(0x15) & 0x40, 0x40);
+    }
+
+    #[test]
+    fn test_inhibit_frame_irq() {
+        let mapper = create_mapper_for_test();
+        let mut apu = Apu::new();
+        let mut interrupts = Interrupts::new();
+        apu.run(29840, &mut interrupts, &*mapper);
+        // Setting the inhibit flag clears the flag immediately.
+        apu.write(0x17, 0x40);
+        assert_eq!(apu.read(0x15) & 0x40, 0x00);
+        apu.run(29840, &mut interrupts, &*mapper);
+        assert_eq!(apu.read(0x15) & 0x40, 0x00);
+        // No IRQ in 5 step mode either.
+        write_frame_counter(&mut apu, 0x80, &*mapper);
+        apu.run(37290, &mut interrupts, &*mapper);
+        assert_eq!(apu.read(0x15) & 0x40, 0x00);
+        assert!(!interrupts.is_irq_asserted());
+    }
+
+    #[test]
+    fn test_five_step_mode_clocks_immediately() {
+        let mapper = create_mapper_for_test();
+        let mut apu = Apu::new();
+        let mut interrupts = Interrupts::new();
+        write_frame_counter(&mut apu, 0x40, &*mapper);
+        apu.write(0x15, 0x01);
+        // Length counter of 2 half frames.
+        apu.write(0x03, 0x18);
+        write_frame_counter(&mut apu, 0xC0, &*mapper);
+        assert_eq!(apu.read(0x15) & 0x01, 0x01);
+        // Written on an odd cycle, the second half frame comes 3 cycles later.
+        apu.write(0x17, 0xC0);
+        apu.run(4, &mut interrupts, &*mapper);
+        apu.run(2, &mut interrupts, &*mapper);
+        assert_eq!(apu.read(0x15) & 0x01, 0x01);
+        apu.run(1, &mut interrupts, &*mapper);
+        assert_eq!(apu.read(0x15) & 0x01, 0x00);
     }
 
-    fn update_envelope(&mut self) {
-        self.squares.0.update_envelope();
-        self.squares.1.update_envelope();
-        self.noise.update_envelope();
+    #[test]
+    fn test_length_counter_timing() {
+        let mapper = create_mapper_for_test();
+        let mut apu = Apu::new();
+        let mut interrupts = Interrupts::new();
+        write_frame_counter(&mut apu, 0x40, &*mapper);
+        apu.write(0x15, 0x01);
+        apu.write(0x03, 0x18);
+        // The sequencer has run for 2 cycles, half frames are at 14913 and 29829.
+        apu.run(29828 - 2, &mut interrupts, &*mapper);
+        assert_eq!(apu.read(0x15) & 0x01, 0x01);
+        apu.run(1, &mut interrupts, &*mapper);
+        assert_eq!(apu.read(0x15) & 0x01, 0x00);
     }
 }
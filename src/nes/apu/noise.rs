@@ -1,139 +1,127 @@
-use super::constants::{NOISE_TIMER_PERIOD_TABLE, GROBAL_GAIN, CPU_CLOCK, COUNTER_TABLE};
+use super::constants::NOISE_TIMER_PERIOD_TABLE;
+use super::envelope::Envelope;
+use super::length_counter::LengthCounter;
+use nes::state::{Snapshot, StateReader, StateWriter};
 use nes::types::{Data, Addr};
 
+// Noise channel ($400C-$400F)
+//
+// A 15bit linear feedback shift register is shifted on each timer clock.
+// The feedback is bit 0 xor bit 1, or bit 0 xor bit 6 in the short mode,
+// which makes a metallic 93 step sequence. The output is muted while bit 0 is set.
 #[derive(Debug)]
 pub struct Noise {
-    envelope_generator_counter: usize,
-    envelope_rate: usize,
-    envelope_volume: usize,
-    envelope_enable: bool,
-    is_length_counter_enable: bool,
-    length_counter: usize,
-
-    divider_for_frequency: usize,
-    frequency: usize,
-    enable: bool,
-}
-
-extern "C" {
-    fn set_noise_frequency(freq: f32);
-    fn set_noise_volume(volume: f32);
-    fn stop_noise();
-    fn start_noise();
-// fn close_noise();
+    envelope: Envelope,
+    length_counter: LengthCounter,
+    timer_period: u16,
+    timer: u16,
+    is_short_mode: bool,
+    shift_register: u16,
 }
 
 impl Noise {
     pub fn new() -> Self {
         Noise {
-            envelope_generator_counter: 0,
-            envelope_rate: 0x0F,
-            envelope_volume: 0x0F,
-            envelope_enable: false,
-
-            is_length_counter_enable: false,
-            length_counter: 0,
-            divider_for_frequency: 1,
-            frequency: 0,
-            enable: false,
+            envelope: Envelope::new(),
+            length_counter: LengthCounter::new(),
+            timer_period: NOISE_TIMER_PERIOD_TABLE[0],
+            timer: 0,
+            is_short_mode: false,
+            // Set to 1 on power up.
+            shift_register: 1,
         }
     }
 
-    pub fn enable(&mut self) {
-        self.enable = true;
-        self.start();
+    pub fn set_enabled(&mut self, is_enabled: bool) {
+        self.length_counter.set_enabled(is_enabled);
     }
 
-    pub fn disable(&mut self) {
-        self.enable = false;
-        self.stop();
+    pub fn has_count_end(&self) -> bool {
+        !self.length_counter.is_active()
     }
 
-
-    fn get_volume(&self) -> f32 {
-        let vol = if self.envelope_enable {
-            self.envelope_volume
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            let tap = if self.is_short_mode { 6 } else { 1 };
+            let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 0x01;
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
         } else {
-            self.envelope_rate
-        };
-        vol as f32 / (16.0 / GROBAL_GAIN)
-    }
-
-    pub fn update_envelope(&mut self) {
-        self.envelope_generator_counter -= 1;
-        if self.envelope_generator_counter <= 0 {
-            self.envelope_generator_counter = self.envelope_rate;
-            if self.envelope_volume > 0 {
-                self.envelope_volume -= 1;
-            } else {
-                self.stop();
-                self.envelope_volume = 0x0F;
-            }
-        }
-        self.set_volume();
-    }
-
-    pub fn start(&self) {
-        unsafe { start_noise() };
-    }
-
-    pub fn stop(&self) {
-        unsafe { stop_noise() };
-    }
-
-    // Length counter
-    // When clocked by the frame counter, the length counter is decremented except when:
-    // The length counter is 0, or The halt flag is set
-    pub fn update_counter(&mut self) {
-        if self.is_length_counter_enable && self.length_counter > 0 {
-            self.length_counter -= 1;
-            if self.length_counter == 0 {
-                self.stop();
-            }
+            self.timer -= 1;
         }
-
     }
 
-    pub fn has_count_end(&self) -> bool {
-        self.length_counter == 0
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
     }
 
-    fn set_volume(&self) {
-        unsafe { set_noise_volume(self.get_volume()) }
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
     }
 
-    fn set_frequency(&self, data: Data) {
-        unsafe {
-            set_noise_frequency(CPU_CLOCK as f32 /
-                                NOISE_TIMER_PERIOD_TABLE[data as usize & 0xF] as f32 /
-                                2f32)
+    // 0-15
+    pub fn get_output(&self) -> Data {
+        if !self.length_counter.is_active() || self.shift_register & 0x01 == 0x01 {
+            0
+        } else {
+            self.envelope.get_volume()
         }
     }
 
     pub fn write(&mut self, addr: Addr, data: Data) {
         match addr {
             0x00 => {
-                self.envelope_enable = (data & 0x10) == 0;
-                self.envelope_rate = data as usize & 0xF;
-                self.is_length_counter_enable = data & 0x20 == 0x00;
-                self.set_volume();
+                self.length_counter.set_halted(data & 0x20 == 0x20);
+                self.envelope.write(data);
             }
             0x02 => {
-                // this.isShortPeriod = !!(data & 0x80);
-                self.set_frequency(data);
-            }    
+                self.is_short_mode = data & 0x80 == 0x80;
+                self.timer_period = NOISE_TIMER_PERIOD_TABLE[data as usize & 0xF];
+            }
             0x03 => {
-                if self.is_length_counter_enable {
-                    self.length_counter = COUNTER_TABLE[(data as usize & 0xF8) >> 3] as usize;
-                }
-                self.envelope_generator_counter = self.envelope_rate;
-                self.envelope_volume = 0x0F;
-                if self.enable {
-                    self.set_volume();
-                    self.start();
-                }
-            }                        
+                self.length_counter.write(data);
+                self.envelope.restart();
+            }
             _ => (),
         }
     }
 }
+
+impl Snapshot for Noise {
+    fn save(&self, writer: &mut StateWriter) {
+        self.envelope.save(writer);
+        self.length_counter.save(writer);
+        writer.write_u16(self.timer_period);
+        writer.write_u16(self.timer);
+        writer.write_bool(self.is_short_mode);
+        writer.write_u16(self.shift_register);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.envelope.load(reader);
+        self.length_counter.load(reader);
+        self.timer_period = reader.read_u16();
+        self.timer = reader.read_u16();
+        self.is_short_mode = reader.read_bool();
+        self.shift_register = reader.read_u16();
+    }
+}
+
+#[test]
+fn test_shift_register_period() {
+    let mut noise = Noise::new();
+    let mut count_period = |is_short_mode: bool| {
+        noise.is_short_mode = is_short_mode;
+        let start = noise.shift_register;
+        (1..40000)
+            .find(|_| {
+                for _ in 0..noise.timer_period {
+                    noise.clock_timer();
+                }
+                noise.shift_register == start
+            })
+            .unwrap()
+    };
+    assert_eq!(count_period(false), 32767);
+    assert_eq!(count_period(true), 93);
+}
@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+// Converts the mixer output at the CPU clock to samples at the output rate.
+//
+// The mixer output is a sum of steps, so each change of the level is added as
+// a windowed sinc impulse at its exact position between output samples and
+// the samples are integrated back to steps. This keeps the steps band limited
+// without filtering every one of the 1.79M input levels a second.
+// A first order high-pass then removes DC as the capacitors on the board do.
+const HALF_WIDTH: usize = 8;
+const WIDTH: usize = HALF_WIDTH * 2;
+// Sub-sample positions of the impulse.
+const PHASES: usize = 32;
+// Cutoff frequency relative to the output rate, slightly under the nyquist frequency.
+const CUTOFF: f64 = 0.45;
+const HIGH_PASS_FREQUENCY: f64 = 90.0;
+
+#[derive(Debug)]
+pub struct Resampler {
+    clock_rate: f64,
+    sample_rate: u32,
+    // Output samples per input clock.
+    ratio: f64,
+    kernel: Vec<[f32; WIDTH]>,
+    // Impulses of the samples not output yet, the front is the next sample.
+    deltas: VecDeque<f32>,
+    // Position of the current clock in output samples from the front of `deltas`.
+    position: f64,
+    level: f32,
+    sum: f32,
+    high_pass_factor: f32,
+    high_pass_input: f32,
+    high_pass_output: f32,
+    samples: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(clock_rate: f64, sample_rate: u32) -> Self {
+        Resampler {
+            clock_rate,
+            sample_rate,
+            ratio: sample_rate as f64 / clock_rate,
+            kernel: build_kernel(),
+            deltas: vec![0.0; WIDTH].into_iter().collect(),
+            position: (HALF_WIDTH - 1) as f64,
+            level: 0.0,
+            sum: 0.0,
+            high_pass_factor: (-2.0 * PI * HIGH_PASS_FREQUENCY / sample_rate as f64).exp() as f32,
+            high_pass_input: 0.0,
+            high_pass_output: 0.0,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Drops the pending samples.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        *self = Resampler::new(self.clock_rate, sample_rate);
+    }
+
+    // Called on each input clock with the current level.
+    pub fn clock(&mut self, level: f32) {
+        if level != self.level {
+            self.add_delta(level - self.level);
+            self.level = level;
+        }
+        self.position += self.ratio;
+        // A sample is complete once no impulse at or after the current clock can reach it.
+        while self.position >= HALF_WIDTH as f64 {
+            self.position -= 1.0;
+            let delta = self.deltas.pop_front().unwrap_or(0.0);
+            self.deltas.push_back(0.0);
+            self.output(delta);
+        }
+    }
+
+    pub fn get_samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn clear_samples(&mut self) {
+        self.samples.clear();
+    }
+
+    fn add_delta(&mut self, delta: f32) {
+        let index = self.position.floor();
+        let phase = ((self.position - index) * PHASES as f64).round() as usize;
+        // The impulse spans from HALF_WIDTH - 1 samples before to HALF_WIDTH samples after.
+        let start = index as usize + 1 - HALF_WIDTH;
+        for (i, k) in self.kernel[phase].iter().enumerate() {
+            self.deltas[start + i] += delta * k;
+        }
+    }
+
+    fn output(&mut self, delta: f32) {
+        self.sum += delta;
+        let output = self.sum - self.high_pass_input + self.high_pass_factor * self.high_pass_output;
+        self.high_pass_input = self.sum;
+        self.high_pass_output = output;
+        self.samples.push(output);
+    }
+}
+
+// Blackman windowed sinc for each phase, normalized so that each impulse integrates to 1.
+fn build_kernel() -> Vec<[f32; WIDTH]> {
+    (0..=PHASES)
+        .map(|phase| {
+            let offset = phase as f64 / PHASES as f64;
+            let mut taps = [0.0; WIDTH];
+            for (i, tap) in taps.iter_mut().enumerate() {
+                let x = i as f64 - (HALF_WIDTH - 1) as f64 - offset;
+                let sinc = if x == 0.0 {
+                    2.0 * CUTOFF
+                } else {
+                    (2.0 * PI * CUTOFF * x).sin() / (PI * x)
+                };
+                let t = x / HALF_WIDTH as f64;
+                let window = 0.42 + 0.5 * (PI * t).cos() + 0.08 * (2.0 * PI * t).cos();
+                *tap = (sinc * window) as f32;
+            }
+            let sum: f32 = taps.iter().sum();
+            for tap in taps.iter_mut() {
+                *tap /= sum;
+            }
+            taps
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sample_count() {
+        let mut resampler = Resampler::new(1_789_772.0, 44_100);
+        for _ in 0..1_789_772 / 60 {
+            resampler.clock(0.0);
+        }
+        assert!((resampler.get_samples().len() as i32 - 735).abs() <= 1);
+    }
+
+    #[test]
+    fn test_step_is_band_limited() {
+        let mut resampler = Resampler::new(1_789_772.0, 44_100);
+        // 1kHz square wave.
+        for i in 0..1_789_772 / 10 {
+            resampler.clock(if (i / 895) % 2 == 0 { 0.5 } else { 0.0 });
+        }
+        let samples = resampler.get_samples();
+        // Without DC after the high-pass settles, with a little overshoot of the steps.
+        let tail = &samples[samples.len() - 441..];
+        let average = tail.iter().sum::<f32>() / tail.len() as f32;
+        assert!(average.abs() < 0.02);
+        let peak = tail.iter().cloned().fold(0.0f32, f32::max);
+        assert!(peak > 0.25 && peak < 0.35);
+    }
+}
@@ -1,239 +1,214 @@
 use super::constants::*;
+use super::envelope::Envelope;
+use super::length_counter::LengthCounter;
+use nes::state::{Snapshot, StateReader, StateWriter};
 use nes::types::{Data, Addr};
 
+// Square channel ($4000-$4003, $4004-$4007)
+//
+// The timer is clocked every other CPU cycle and steps the 8 step duty sequencer.
+// The sweep unit bends the period every half frame.
 #[derive(Debug)]
 pub struct Square {
     index: usize,
-    sweep_unit_counter: usize,
-    length_counter: usize,
-    is_length_counter_enable: bool,
-    sweep_unit_divider: usize,
-    frequency: usize,
-    sweep_shift_amount: usize,
+    duty: usize,
+    step: usize,
+    timer_period: u16,
+    timer: u16,
+    envelope: Envelope,
+    length_counter: LengthCounter,
     is_sweep_enabled: bool,
-    sweep_mode: bool,
-    divider_for_frequency: usize,
-    envelope_loop_enable: bool,
-    envelope_generator_counter: usize,
-    envelope_rate: usize,
-    envelope_volume: usize,
-    envelope_enable: bool,
-    enable: bool,
-    playing: bool,
-}
-
-extern "C" {
-    fn start_oscillator(index: usize);
-    fn stop_oscillator(index: usize);
-    // fn close_oscillator(index: usize);
-    fn set_oscillator_frequency(index: usize, freq: usize);
-    fn change_oscillator_frequency(index: usize, freq: usize);
-    fn set_oscillator_volume(index: usize, volume: f32);
-    fn set_oscillator_pulse_width(index: usize, width: f32);
+    is_sweep_negated: bool,
+    is_sweep_reloaded: bool,
+    sweep_period: Data,
+    sweep_divider: Data,
+    sweep_shift_amount: Data,
 }
 
 impl Square {
+    // The sweep of index 0 negates with ones' complement, index 1 with two's complement.
     pub fn new(index: usize) -> Self {
         Square {
             index,
-            sweep_unit_counter: 0,
-            length_counter: 0,
-            sweep_unit_divider: 1,
-            frequency: 0,
-            sweep_shift_amount: 0,
+            duty: 0,
+            step: 0,
+            timer_period: 0,
+            timer: 0,
+            envelope: Envelope::new(),
+            length_counter: LengthCounter::new(),
             is_sweep_enabled: false,
-            sweep_mode: false,
-            divider_for_frequency: 1,
-            envelope_loop_enable: false,
-            envelope_generator_counter: 0,
-            envelope_rate: 0x0F,
-            envelope_volume: 0,
-            envelope_enable: false,
-            is_length_counter_enable: false,
-            enable: false,
-            playing: false,
+            is_sweep_negated: false,
+            is_sweep_reloaded: false,
+            sweep_period: 0,
+            sweep_divider: 0,
+            sweep_shift_amount: 0,
         }
     }
 
-    fn get_volume(&self) -> f32 {
-        let vol = if self.envelope_enable {
-            self.envelope_volume
-        } else {
-            self.envelope_rate
-        };
-        vol as f32 / (GROBAL_GAIN)
+    pub fn set_enabled(&mut self, is_enabled: bool) {
+        self.length_counter.set_enabled(is_enabled);
     }
 
-    // fn stop_oscillator(&mut self) {
-    //     unsafe {
-    //         stop_oscillator(self.index);
-    //     };
-    // }
-
-    // Length counter
-    // When clocked by the frame counter, the length counter is decremented except when:
-    // The length counter is 0, or The halt flag is set
-    pub fn update_counters(&mut self) {
-        if self.is_length_counter_enable && self.length_counter > 0 {
-            self.length_counter -= 1;
-            if self.length_counter == 0 {
-                self.stop();
-            }
-        }
-
-        if !self.is_sweep_enabled || !self.playing {
-            return;
-        };
-
-        self.sweep_unit_counter += 1;
-        if self.sweep_unit_counter % self.sweep_unit_divider == 0 {
-            self.sweep_unit_counter = 0;
-            // INFO:
-            // sweep mode 0 : newPeriod = currentPeriod - (currentPeriod >> N)
-            // sweep mode 1 : newPeriod = currentPeriod + (currentPeriod >> N)
-            if self.sweep_mode {
-                self.divider_for_frequency = self.divider_for_frequency -
-                                             (self.divider_for_frequency >>
-                                              self.sweep_shift_amount);
-            } else {
-                self.divider_for_frequency = self.divider_for_frequency +
-                                             (self.divider_for_frequency >>
-                                              self.sweep_shift_amount);
-
-            };
-            if self.divider_for_frequency > 0x7FF {
-                self.stop();
-            } else if self.divider_for_frequency < 8 {
-                self.stop();
-            }
-            self.update_frequency();
-            self.change_frequency();
-        }
+    pub fn has_count_end(&self) -> bool {
+        !self.length_counter.is_active()
     }
 
-    pub fn enable(&mut self) {
-        self.enable = true;
-        if !self.frequency != 0 {
-            self.start();
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.step = (self.step + 1) % 8;
+        } else {
+            self.timer -= 1;
         }
     }
 
-    pub fn disable(&mut self) {
-        self.enable = false;
-        self.stop();
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
     }
 
-    pub fn start(&mut self) {
-        if !self.playing {
-            self.playing = true;
-            unsafe {
-                start_oscillator(self.index);
-                set_oscillator_frequency(self.index, self.frequency);
-            };
-        } else {
-            self.change_frequency();
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+        let can_sweep = self.is_sweep_enabled && self.sweep_shift_amount > 0 && !self.is_muted();
+        if self.sweep_divider == 0 && can_sweep {
+            self.timer_period = self.get_sweep_target();
         }
-    }
-
-    pub fn stop(&mut self) {
-        if self.playing {
-            self.playing = false;
-            unsafe {
-                stop_oscillator(self.index);
-            };
+        if self.sweep_divider == 0 || self.is_sweep_reloaded {
+            self.sweep_divider = self.sweep_period;
+            self.is_sweep_reloaded = false;
+        } else {
+            self.sweep_divider -= 1;
         }
     }
 
-    pub fn get_pulse_width(&self, duty: usize) -> f32 {
-        match duty {
-            0x00 => 0.125,
-            0x01 => 0.25,
-            0x02 => 0.5,
-            0x03 => 0.75,
-            _ => 0.0,
+    // 0-15
+    pub fn get_output(&self) -> Data {
+        let is_high = DUTY_TABLE[self.duty][self.step] == 1;
+        if is_high && self.length_counter.is_active() && !self.is_muted() {
+            self.envelope.get_volume()
+        } else {
+            0
         }
     }
 
-    pub fn has_count_end(&self) -> bool {
-        self.length_counter == 0
-    }
-
-    pub fn update_envelope(&mut self) {
-        self.envelope_generator_counter -= 1;
-        if self.envelope_generator_counter <= 0 {
-            self.envelope_generator_counter = self.envelope_rate;
-            if self.envelope_volume > 0 {
-                self.envelope_volume -= 1;
-            } else {
-                self.envelope_volume = if self.envelope_loop_enable {
-                    0x0F
-                } else {
-                    0x00
-                };
-            }
+    // sweep mode 0 : newPeriod = currentPeriod + (currentPeriod >> N)
+    // sweep mode 1 : newPeriod = currentPeriod - (currentPeriod >> N)
+    fn get_sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift_amount;
+        if !self.is_sweep_negated {
+            self.timer_period + change
+        } else if self.index == 0 {
+            self.timer_period.saturating_sub(change + 1)
+        } else {
+            self.timer_period.saturating_sub(change)
         }
-        unsafe {
-            set_oscillator_volume(self.index, self.get_volume());
-        };
     }
 
-    fn change_frequency(&self) {
-        unsafe {
-            change_oscillator_frequency(self.index, self.frequency);
-        }
+    // Too high or too low periods are muted even while the sweep is disabled.
+    fn is_muted(&self) -> bool {
+        self.timer_period < 8 || self.get_sweep_target() > 0x7FF
     }
 
-    // fn reset(&mut self) {
-    //     self.length_counter = 0;
-    //     self.is_length_counter_enable = false;
-    // }
-
     pub fn write(&mut self, addr: Addr, data: Data) {
         match addr {
             0x00 => {
-                self.envelope_enable = data & 0x10 == 0;
-                self.envelope_rate = data as usize & 0xF;
-                self.envelope_loop_enable = (data & 0x10) != 0;
-                let duty = (data >> 6) & 0x3;
-                self.is_length_counter_enable = data & 0x20 == 0x00;
-                unsafe {
-                    set_oscillator_volume(self.index, self.get_volume());
-                    set_oscillator_pulse_width(self.index, self.get_pulse_width(duty as usize));
-                }
+                self.duty = (data >> 6) as usize;
+                self.length_counter.set_halted(data & 0x20 == 0x20);
+                self.envelope.write(data);
             }
             0x01 => {
                 // Sweep
                 self.is_sweep_enabled = data & 0x80 == 0x80;
-                self.sweep_unit_divider = ((data as usize >> 4) & 0x07) + 1;
-                self.sweep_mode = data & 0x08 == 0x08;
-                self.sweep_shift_amount = data as usize & 0x07;
+                self.sweep_period = (data >> 4) & 0x07;
+                self.is_sweep_negated = data & 0x08 == 0x08;
+                self.sweep_shift_amount = data & 0x07;
+                self.is_sweep_reloaded = true;
             }
             0x02 => {
-                self.divider_for_frequency = (self.divider_for_frequency & 0x700) | data as usize;
-                self.update_frequency();
-                self.change_frequency();
-            }    
+                self.timer_period = (self.timer_period & 0x700) | data as u16;
+            }
             0x03 => {
                 // Programmable timer, length counter
-                self.divider_for_frequency &= 0xFF;
-                self.divider_for_frequency |= (data as usize & 0x7) << 8;
-                if self.is_length_counter_enable {
-                    self.length_counter = COUNTER_TABLE[(data & 0xF8) as usize >> 3] as usize / 2;
-                }
-                self.update_frequency();
-                self.sweep_unit_counter = 0;
-                // envelope
-                self.envelope_generator_counter = self.envelope_rate;
-                self.envelope_volume = 0x0F;
-                if self.enable {
-                    self.start();
-                }
-            }                        
+                self.timer_period = (self.timer_period & 0xFF) | (data as u16 & 0x7) << 8;
+                self.length_counter.write(data);
+                self.step = 0;
+                self.envelope.restart();
+            }
             _ => (),
         }
     }
+}
+
+impl Snapshot for Square {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_usize(self.duty);
+        writer.write_usize(self.step);
+        writer.write_u16(self.timer_period);
+        writer.write_u16(self.timer);
+        self.envelope.save(writer);
+        self.length_counter.save(writer);
+        writer.write_bool(self.is_sweep_enabled);
+        writer.write_bool(self.is_sweep_negated);
+        writer.write_bool(self.is_sweep_reloaded);
+        writer.write_u8(self.sweep_period);
+        writer.write_u8(self.sweep_divider);
+        writer.write_u8(self.sweep_shift_amount);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.duty = reader.read_usize();
+        self.step = reader.read_usize();
+        self.timer_period = reader.read_u16();
+        self.timer = reader.read_u16();
+        self.envelope.load(reader);
+        self.length_counter.load(reader);
+        self.is_sweep_enabled = reader.read_bool();
+        self.is_sweep_negated = reader.read_bool();
+        self.is_sweep_reloaded = reader.read_bool();
+        self.sweep_period = reader.read_u8();
+        self.sweep_divider = reader.read_u8();
+        self.sweep_shift_amount = reader.read_u8();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_duty_sequence() {
+        let mut square = Square::new(0);
+        square.set_enabled(true);
+        // 25% duty, constant volume 10, period 8.
+        square.write(0x00, 0x5A);
+        square.write(0x02, 0x08);
+        square.write(0x03, 0x08);
+        let mut outputs = Vec::new();
+        for _ in 0..8 {
+            outputs.push(square.get_output());
+            for _ in 0..9 {
+                square.clock_timer();
+            }
+        }
+        assert_eq!(outputs, [0, 10, 10, 0, 0, 0, 0, 0]);
+    }
 
-    fn update_frequency(&mut self) {
-        self.frequency = CPU_CLOCK / ((self.divider_for_frequency + 1) * 16) as usize;
+    #[test]
+    fn test_sweep_mutes_and_negates() {
+        let mut square = Square::new(0);
+        square.set_enabled(true);
+        square.write(0x00, 0x3F);
+        square.write(0x02, 0xFF);
+        square.write(0x03, 0x07);
+        // 0x7FF + (0x7FF >> 1) overflows even without enabling the sweep.
+        square.write(0x01, 0x01);
+        assert!(square.is_muted());
+        // Ones' complement on the first channel, two's complement on the second.
+        square.write(0x01, 0x09);
+        assert_eq!(square.get_sweep_target(), 0x7FF - 0x3FF - 1);
+        let mut square = Square::new(1);
+        square.write(0x02, 0xFF);
+        square.write(0x03, 0x07);
+        square.write(0x01, 0x09);
+        assert_eq!(square.get_sweep_target(), 0x7FF - 0x3FF);
     }
 }
@@ -1,146 +1,127 @@
 use super::constants::*;
+use super::length_counter::LengthCounter;
+use nes::state::{Snapshot, StateReader, StateWriter};
 use nes::types::{Data, Addr};
 
+// Triangle channel ($4008-$400B)
+//
+// The timer is clocked every CPU cycle and steps the 32 step sequence
+// while both the length counter and the linear counter are non-zero.
+// The sequencer holds the last value when stopped, so there is no pop.
 #[derive(Debug)]
 pub struct Triangle {
-    index: usize,
-    is_length_counter_enable: bool,
-    length_counter: usize,
-    linear_counter: usize,
-    divider_for_frequency: usize,
-    frequency: usize,
-    enable: bool,
-    playing: bool,
-}
-
-extern "C" {
-    fn start_oscillator(index: usize);
-    fn stop_oscillator(index: usize);
-    fn close_oscillator(index: usize);
-    fn set_oscillator_frequency(index: usize, freq: usize);
-    fn change_oscillator_frequency(index: usize, freq: usize);
-    fn set_oscillator_volume(index: usize, volume: f32);
-    fn set_oscillator_pulse_width(index: usize, width: f32);
+    step: usize,
+    timer_period: u16,
+    timer: u16,
+    length_counter: LengthCounter,
+    linear_counter: Data,
+    linear_counter_period: Data,
+    is_linear_counter_reloaded: bool,
+    // Halts the length counter and keeps reloading the linear counter.
+    is_control: bool,
 }
 
 impl Triangle {
-    pub fn new(index: usize) -> Self {
+    pub fn new() -> Self {
         Triangle {
-            index,
-            is_length_counter_enable: false,
-            length_counter: 0,
+            step: 0,
+            timer_period: 0,
+            timer: 0,
+            length_counter: LengthCounter::new(),
             linear_counter: 0,
-            divider_for_frequency: 1,
-            frequency: 0,
-            enable: false,
-            playing: false,
+            linear_counter_period: 0,
+            is_linear_counter_reloaded: false,
+            is_control: false,
         }
     }
 
-    fn get_volume(&self) -> f32 {
-        32.0 / (16.0 / GROBAL_GAIN)
-    }
-
-    fn stop_oscillator(&mut self) {
-        // self.length_counter = 0;
-        // self.linear_counter = 0;
-        unsafe {
-            stop_oscillator(self.index);
-            set_oscillator_volume(self.index, 0.0);
-        };
+    pub fn set_enabled(&mut self, is_enabled: bool) {
+        self.length_counter.set_enabled(is_enabled);
     }
 
-    pub fn enable(&mut self) {
-        self.enable = true;
-        self.start();
-    }
-
-    pub fn disable(&mut self) {
-        self.enable = false;
-        self.stop();
+    pub fn has_count_end(&self) -> bool {
+        !self.length_counter.is_active()
     }
 
-    pub fn stop(&mut self) {
-        if self.playing {
-            self.playing = false;
-            self.stop_oscillator();
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            // Periods under 2 are ultrasonic, stopping them avoids an audible pop.
+            if self.length_counter.is_active() && self.linear_counter > 0 && self.timer_period >= 2 {
+                self.step = (self.step + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
         }
     }
 
-    // Length counter
-    // When clocked by the frame counter, the length counter is decremented except when:
-    // The length counter is 0, or The halt flag is set
-    pub fn update_counter(&mut self) {
-        if self.is_length_counter_enable && self.length_counter > 0 {
-            self.length_counter -= 1;
-        }
-        if self.linear_counter > 0 {
+    pub fn clock_quarter_frame(&mut self) {
+        if self.is_linear_counter_reloaded {
+            self.linear_counter = self.linear_counter_period;
+        } else if self.linear_counter > 0 {
             self.linear_counter -= 1;
         }
-        if !self.is_length_counter_enable {
-            return;
-        }
-        if self.length_counter == 0 || self.linear_counter == 0 {
-            self.stop();
+        if !self.is_control {
+            self.is_linear_counter_reloaded = false;
         }
     }
 
-    fn change_frequency(&self) {
-        unsafe {
-            change_oscillator_frequency(self.index, self.frequency);
-        }
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
     }
 
-    pub fn start(&mut self) {
-        if !self.playing {
-            self.playing = true;
-            unsafe {
-                start_oscillator(self.index);
-                set_oscillator_frequency(self.index, self.frequency);
-            };
-        } else {
-            self.change_frequency();
-        }
-    }
-
-    pub fn has_count_end(&self) -> bool {
-        self.length_counter == 0
-    }
-
-    fn set_volume(&self) {
-        unsafe { set_oscillator_volume(self.index, self.get_volume()) }
+    // 0-15
+    pub fn get_output(&self) -> Data {
+        TRIANGLE_SEQUENCE[self.step]
     }
 
     pub fn write(&mut self, addr: Addr, data: Data) {
         match addr {
             0x00 => {
-                self.is_length_counter_enable = data & 0x80 == 0;
-                self.linear_counter = data as usize & 0x7F;
+                self.is_control = data & 0x80 == 0x80;
+                self.length_counter.set_halted(self.is_control);
+                self.linear_counter_period = data & 0x7F;
             }
             0x02 => {
-                self.divider_for_frequency &= 0x700;
-                self.divider_for_frequency |= data as usize;
-                self.update_frequency();
-                self.change_frequency();
-            }    
+                self.timer_period = (self.timer_period & 0x700) | data as u16;
+            }
             0x03 => {
                 // Programmable timer, length counter
-                self.divider_for_frequency &= 0xFF;
-                self.divider_for_frequency |= (data as usize & 0x7) << 8;
-                if self.is_length_counter_enable {
-                    self.length_counter = COUNTER_TABLE[(data & 0xF8) as usize >> 3] as usize / 2;
-                }
-                self.update_frequency();
-                self.set_volume();
-                if self.enable {
-                    self.start();
-                }
-            }                        
+                self.timer_period = (self.timer_period & 0xFF) | (data as u16 & 0x7) << 8;
+                self.length_counter.write(data);
+                self.is_linear_counter_reloaded = true;
+            }
             _ => (),
         }
     }
+}
+
+impl Default for Triangle {
+    fn default() -> Self {
+        Triangle::new()
+    }
+}
+
+impl Snapshot for Triangle {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_usize(self.step);
+        writer.write_u16(self.timer_period);
+        writer.write_u16(self.timer);
+        self.length_counter.save(writer);
+        writer.write_u8(self.linear_counter);
+        writer.write_u8(self.linear_counter_period);
+        writer.write_bool(self.is_linear_counter_reloaded);
+        writer.write_bool(self.is_control);
+    }
 
-    fn update_frequency(&mut self) {
-        self.frequency = CPU_CLOCK / ((self.divider_for_frequency + 1) * 32) as usize;
+    fn load(&mut self, reader: &mut StateReader) {
+        self.step = reader.read_usize();
+        self.timer_period = reader.read_u16();
+        self.timer = reader.read_u16();
+        self.length_counter.load(reader);
+        self.linear_counter = reader.read_u8();
+        self.linear_counter_period = reader.read_u8();
+        self.is_linear_counter_reloaded = reader.read_bool();
+        self.is_control = reader.read_bool();
     }
 }
@@ -0,0 +1,42 @@
+// Plays the PCM samples generated by the APU.
+// Each frame of samples is queued right after the previous one,
+// a little ahead of the current time so that late frames don't crackle.
+const LATENCY = 0.05;
+
+export default class Speaker {
+
+  constructor() {
+    try {
+      const AudioContext = window.AudioContext || window.webkitAudioContext
+      this.context = new AudioContext();
+    } catch (e) {
+      throw new Error('Web Audio isn\'t supported in this browser!');
+    }
+    this.nextTime = 0;
+  }
+
+  get sampleRate() {
+    return this.context.sampleRate;
+  }
+
+  play(samples) {
+    if (samples.length === 0) return;
+    const buffer = this.context.createBuffer(1, samples.length, this.context.sampleRate);
+    // Copy before the wasm memory is reused by the next frame.
+    buffer.getChannelData(0).set(samples);
+    const source = this.context.createBufferSource();
+    source.buffer = buffer;
+    source.connect(this.context.destination);
+    const now = this.context.currentTime;
+    // Restart the queue after an underrun or when it has drifted too far ahead.
+    if (this.nextTime < now || this.nextTime > now + LATENCY * 4) {
+      this.nextTime = now + LATENCY;
+    }
+    source.start(this.nextTime);
+    this.nextTime += buffer.duration;
+  }
+
+  close() {
+    this.context.close();
+  }
+}
@@ -1,19 +1,17 @@
 use nes::apu::Apu;
 use nes::dma::Dma;
 use nes::keypad::Keypad;
-use nes::mmc::*;
+use nes::mapper::Mapper;
 use nes::ppu::Ppu;
 use nes::ram::Ram;
-use nes::rom::Rom;
 
 pub struct Bus<'a> {
-    program_rom: &'a Rom,
     work_ram: &'a mut Ram,
     ppu: &'a mut Ppu,
     apu: &'a mut Apu,
     keypad: &'a mut Keypad,
     dma: &'a mut Dma,
-    mmc: &'a mut Mmc,
+    mapper: &'a mut dyn Mapper,
 }
 
 pub trait CpuBus {
@@ -26,22 +24,30 @@ pub trait CpuBus {
 
 impl<'a> Bus<'a> {
     pub fn new(
-        program_rom: &'a Rom,
         work_ram: &'a mut Ram,
         ppu: &'a mut Ppu,
         apu: &'a mut Apu,
         keypad: &'a mut Keypad,
         dma: &'a mut Dma,
-        mmc: &'a mut Mmc,
+        mapper: &'a mut dyn Mapper,
     ) -> Bus<'a> {
         Self {
-            program_rom,
             work_ram,
             ppu,
             apu,
             keypad,
             dma,
-            mmc,
+            mapper,
+        }
+    }
+
+    // Reads without side effects to show in traces,
+    // registers of PPU and APU read as 0xFF like in the logs of Nintendulator.
+    pub fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x1FFF => self.work_ram.read(addr & 0x07FF),
+            0x2000..=0x401F => 0xFF,
+            _ => self.mapper.read(addr),
         }
     }
 }
@@ -55,45 +61,23 @@ impl<'a> CpuBus for Bus<'a> {
 
     fn read(&mut self, addr: u16) -> u8 {
         match addr {
-            0x0000...0x1FFF => self.work_ram.read(addr & 0x07FF),
-            0x2000...0x3FFF => self.ppu.read(addr - 0x2000),
+            0x0000..=0x1FFF => self.work_ram.read(addr & 0x07FF),
+            0x2000..=0x3FFF => self.ppu.read(addr - 0x2000, self.mapper),
             0x4016 => self.keypad.read(),
             0x4017 => 0, // TODO: 2player
-            0x4000...0x401F => self.apu.read(addr - 0x4000),
-            0x6000...0x7FFF => {
-                println!(
-                    "Not implemented. This area is battery backup ram area 0x{:x}",
-                    addr
-                );
-                0
-            }
-            0x8000...0xBFFF => self.program_rom.read(addr - 0x8000),
-            0xC000...0xFFFF if self.program_rom.size() <= 0x4000 => {
-                self.program_rom.read(addr - 0xC000)
-            }
-            0xC000...0xFFFF => self.program_rom.read(addr - 0x8000),
-            _ => panic!("[READ] There is an illegal address (0x{:x}) access.", addr),
+            0x4000..=0x401F => self.apu.read(addr - 0x4000),
+            _ => self.mapper.read(addr),
         }
     }
 
     fn write(&mut self, addr: u16, data: u8) {
         match addr {
-            0x0000...0x1FFF => self.work_ram.write(addr & 0x07FF, data),
-            0x2000...0x3FFF => self.ppu.write(addr - 0x2000, data),
+            0x0000..=0x1FFF => self.work_ram.write(addr & 0x07FF, data),
+            0x2000..=0x3FFF => self.ppu.write(addr - 0x2000, data, self.mapper),
             0x4014 => self.dma.write(data),
             0x4016 => self.keypad.write(data),
-            0x4000...0x401F => self.apu.write(addr - 0x4000, data),
-            0x6000...0x7FFF => {
-                println!(
-                    "Not implemented. This area is battery backup ram area 0x{:x}",
-                    addr
-                );
-            }
-            0x8000...0xFFFF => {
-                println!("switch bank to {}", data);
-                self.mmc.set_bank(data);
-            }
-            _ => panic!("[WRITE] There is an illegal address (0x{:x}) access.", addr),
+            0x4000..=0x401F => self.apu.write_from_cpu(addr - 0x4000, data),
+            _ => self.mapper.write(addr, data),
         };
     }
 }
@@ -9,24 +9,25 @@ pub fn fetch<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Data
     code
 }
 
+// Returns the operand and the additional cycle taken when indexing crosses a page.
 pub fn fetch_operand<T: CpuRegisters, U: CpuBus>(code: &Opecode,
                                                  registers: &mut T,
                                                  bus: &mut U)
-                                                 -> Word {
+                                                 -> (Word, Data) {
     match code.mode {
-        Addressing::Accumulator => 0x0000,
-        Addressing::Implied => 0x0000,
-        Addressing::Immediate => fetch(registers, bus) as Word,
-        Addressing::Relative => fetch_relative(registers, bus),
-        Addressing::ZeroPage => fetch(registers, bus) as Word,
-        Addressing::ZeroPageX => fetch_zeropage_x(registers, bus),
-        Addressing::ZeroPageY => fetch_zeropage_y(registers, bus),
-        Addressing::Absolute => fetch_word(registers, bus),     
+        Addressing::Accumulator => (0x0000, 0),
+        Addressing::Implied => (0x0000, 0),
+        Addressing::Immediate => (fetch(registers, bus) as Word, 0),
+        Addressing::Relative => (fetch_relative(registers, bus), 0),
+        Addressing::ZeroPage => (fetch(registers, bus) as Word, 0),
+        Addressing::ZeroPageX => (fetch_zeropage_x(registers, bus), 0),
+        Addressing::ZeroPageY => (fetch_zeropage_y(registers, bus), 0),
+        Addressing::Absolute => (fetch_word(registers, bus), 0),
         Addressing::AbsoluteX => fetch_absolute_x(registers, bus),
         Addressing::AbsoluteY => fetch_absolute_y(registers, bus),
-        Addressing::PreIndexedIndirect => fetch_pre_indexed_indirect(registers, bus),
+        Addressing::PreIndexedIndirect => (fetch_pre_indexed_indirect(registers, bus), 0),
         Addressing::PostIndexedIndirect => fetch_post_indexed_indirect(registers, bus),
-        Addressing::IndirectAbsolute => fetch_indirect_absolute(registers, bus),
+        Addressing::IndirectAbsolute => (fetch_indirect_absolute(registers, bus), 0),
     }
 }
 
@@ -41,9 +42,9 @@ pub fn fetch_word<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) ->
 pub fn fetch_relative<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Word {
     let base = fetch(registers, bus) as Word;
     if base < 0x80 {
-        base + registers.get_PC()
+        base.wrapping_add(registers.get_PC())
     } else {
-        base + registers.get_PC() - 256
+        base.wrapping_add(registers.get_PC()).wrapping_sub(256)
     }
 }
 
@@ -57,30 +58,30 @@ pub fn fetch_zeropage_y<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut
     (addr + registers.get_Y() as Word) & 0xFF as Word
 }
 
-pub fn fetch_absolute_x<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Word {
+pub fn fetch_absolute_x<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> (Word, Data) {
     let addr = fetch_word(registers, bus);
-    (addr + registers.get_X() as Word) & 0xFFFF
+    add_index(addr, registers.get_X())
 }
 
-pub fn fetch_absolute_y<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Word {
+pub fn fetch_absolute_y<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> (Word, Data) {
     let addr = fetch_word(registers, bus);
-    (addr + registers.get_Y() as Word) & 0xFFFF
+    add_index(addr, registers.get_Y())
 }
 
 pub fn fetch_pre_indexed_indirect<T: CpuRegisters, U: CpuBus>(registers: &mut T,
                                                               bus: &mut U)
                                                               -> Word {
-    let addr = ((fetch(registers, bus) + registers.get_X()) & 0xFF) as Addr;
+    let addr = fetch(registers, bus).wrapping_add(registers.get_X()) as Addr;
     let addr = (bus.read(addr) as Addr) + ((bus.read((addr + 1) as Addr & 0xFF) as Addr) << 8);
     addr & 0xFFFF
 }
 
 pub fn fetch_post_indexed_indirect<T: CpuRegisters, U: CpuBus>(registers: &mut T,
                                                                bus: &mut U)
-                                                               -> Word {
+                                                               -> (Word, Data) {
     let addr = fetch(registers, bus) as Addr;
-    let base_addr = (bus.read(addr) as usize) + ((bus.read((addr + 1) & 0x00FF) as usize) * 0x100);
-    ((base_addr + (registers.get_Y() as usize)) & 0xFFFF) as u16
+    let base_addr = (bus.read(addr) as Addr) | ((bus.read((addr + 1) & 0x00FF) as Addr) << 8);
+    add_index(base_addr, registers.get_Y())
 }
 
 pub fn fetch_indirect_absolute<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) -> Word {
@@ -89,3 +90,9 @@ pub fn fetch_indirect_absolute<T: CpuRegisters, U: CpuBus>(registers: &mut T, bu
     let addr = (bus.read(addr) as Addr) + (upper << 8) as Addr;
     addr & 0xFFFF
 }
+
+fn add_index(base: Word, index: Data) -> (Word, Data) {
+    let addr = base.wrapping_add(index as Word);
+    let additional_cycle = if (base ^ addr) & 0xFF00 != 0 { 1 } else { 0 };
+    (addr, additional_cycle)
+}
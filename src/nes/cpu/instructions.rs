@@ -13,6 +13,15 @@ pub fn process_nmi<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
     registers.set_PC(next);
 }
 
+pub fn process_irq<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
+    registers.set_break(false);
+    push_pc(registers, bus);
+    push_status(registers, bus);
+    registers.set_interrupt(true);
+    let next = bus.read_word(0xFFFE);
+    registers.set_PC(next);
+}
+
 pub fn lda<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
     let computed = bus.read(operand);
     registers
@@ -115,15 +124,15 @@ pub fn tsx<T: CpuRegisters>(registers: &mut T) {
         .update_zero_by(sp);
 }
 
+// The break flag only exists on the stack, it's pushed set by PHP and BRK.
 pub fn php<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
-    registers.set_break(true);
-    push_status(registers, bus);
+    let status = registers.get_P() | 0x10;
+    push(status, registers, bus);
 }
 
 pub fn plp<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
-    registers.set_reserved(true);
     let status = pop(registers, bus);
-    registers.set_P(status);
+    registers.set_P(status).set_break(false).set_reserved(true);
 }
 
 pub fn pha<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
@@ -316,6 +325,8 @@ pub fn asl_acc<T: CpuRegisters>(registers: &mut T) {
 
 pub fn asl<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
     let fetched = bus.read(operand);
+    // Read-modify-write instructions write the unmodified value back first.
+    bus.write(operand, fetched);
     let shifted = (fetched << 1) as u8;
     registers
         .set_carry(fetched & 0x80 == 0x80)
@@ -336,6 +347,7 @@ pub fn lsr_acc<T: CpuRegisters>(registers: &mut T) {
 
 pub fn lsr<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
     let fetched = bus.read(operand);
+    bus.write(operand, fetched);
     let shifted = (fetched >> 1) as u8;
     registers
         .set_carry(fetched & 0x01 == 0x01)
@@ -356,6 +368,7 @@ pub fn rol_acc<T: CpuRegisters>(registers: &mut T) {
 
 pub fn rol<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
     let fetched = bus.read(operand);
+    bus.write(operand, fetched);
     let rotated = rotate_to_left(registers, fetched);
     registers
         .set_carry(fetched & 0x80 == 0x80)
@@ -376,6 +389,7 @@ pub fn ror_acc<T: CpuRegisters>(registers: &mut T) {
 
 pub fn ror<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
     let fetched = bus.read(operand);
+    bus.write(operand, fetched);
     let rotated = rotate_to_right(registers, fetched);
     registers
         .set_carry(fetched & 0x01 == 0x01)
@@ -385,7 +399,7 @@ pub fn ror<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &m
 }
 
 pub fn inx<T: CpuRegisters>(registers: &mut T) {
-    let x = registers.get_X() + 1;
+    let x = registers.get_X().wrapping_add(1);
     registers
         .set_X(x)
         .update_negative_by(x)
@@ -393,7 +407,7 @@ pub fn inx<T: CpuRegisters>(registers: &mut T) {
 }
 
 pub fn iny<T: CpuRegisters>(registers: &mut T) {
-    let y = registers.get_Y() + 1;
+    let y = registers.get_Y().wrapping_add(1);
     registers
         .set_Y(y)
         .update_negative_by(y)
@@ -401,33 +415,35 @@ pub fn iny<T: CpuRegisters>(registers: &mut T) {
 }
 
 pub fn inc<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
-    let data = bus.read(operand) + 1 as u8;
+    let fetched = bus.read(operand);
+    bus.write(operand, fetched);
+    let data = fetched.wrapping_add(1);
     registers.update_negative_by(data).update_zero_by(data);
     bus.write(operand, data);
 }
 
 pub fn dex<T: CpuRegisters>(registers: &mut T) {
-    let x = registers.get_X() as i8 - 1;
+    let x = registers.get_X().wrapping_sub(1);
     registers
-        .set_X(x as Data)
-        .update_negative_by(x as Data)
-        .update_zero_by(x as Data);
+        .set_X(x)
+        .update_negative_by(x)
+        .update_zero_by(x);
 }
 
 pub fn dey<T: CpuRegisters>(registers: &mut T) {
-    let y = registers.get_Y() as i8 - 1;
+    let y = registers.get_Y().wrapping_sub(1);
     registers
-        .set_Y(y as Data)
-        .update_negative_by(y as Data)
-        .update_zero_by(y as Data);
+        .set_Y(y)
+        .update_negative_by(y)
+        .update_zero_by(y);
 }
 
 pub fn dec<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
-    let data = bus.read(operand) as i8 - 1;
-    registers
-        .update_negative_by(data as Data)
-        .update_zero_by(data as Data);
-    bus.write(operand, data as Data);
+    let fetched = bus.read(operand);
+    bus.write(operand, fetched);
+    let data = fetched.wrapping_sub(1);
+    registers.update_negative_by(data).update_zero_by(data);
+    bus.write(operand, data);
 }
 
 pub fn clc<T: CpuRegisters>(registers: &mut T) {
@@ -451,22 +467,18 @@ pub fn sei<T: CpuRegisters>(registers: &mut T) {
 }
 
 pub fn brk<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
-    let interrupt = registers.get_interrupt();
+    // BRK skips the padding byte following the opecode.
     registers.inc_PC();
     push_pc(registers, bus);
-    registers.set_break(true);
-    push_status(registers, bus);
+    let status = registers.get_P() | 0x10;
+    push(status, registers, bus);
     registers.set_interrupt(true);
-    // Ignore interrupt when already set.
-    if !interrupt {
-        let fetched = bus.read_word(0xFFFE);
-        registers.set_PC(fetched);
-    }
-    registers.dec_PC();
+    let fetched = bus.read_word(0xFFFE);
+    registers.set_PC(fetched);
 }
 
 pub fn jsr<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
-    let pc = registers.get_PC() - 1;
+    let pc = registers.get_PC().wrapping_sub(1);
     push((pc >> 8) as u8, registers, bus);
     push(pc as u8, registers, bus);
     registers.set_PC(operand);
@@ -479,7 +491,7 @@ pub fn jmp<T: CpuRegisters>(operand: Word, registers: &mut T) {
 pub fn rti<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
     pop_status(registers, bus);
     pop_pc(registers, bus);
-    registers.set_reserved(true);
+    registers.set_break(false).set_reserved(true);
 }
 
 pub fn rts<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
@@ -487,51 +499,67 @@ pub fn rts<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
     registers.inc_PC();
 }
 
-pub fn bcc<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bcc<T: CpuRegisters>(operand: Word, registers: &mut T) -> Data {
     if !registers.get_carry() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bcs<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bcs<T: CpuRegisters>(operand: Word, registers: &mut T) -> Data {
     if registers.get_carry() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn beq<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn beq<T: CpuRegisters>(operand: Word, registers: &mut T) -> Data {
     if registers.get_zero() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bmi<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bmi<T: CpuRegisters>(operand: Word, registers: &mut T) -> Data {
     if registers.get_negative() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bne<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bne<T: CpuRegisters>(operand: Word, registers: &mut T) -> Data {
     if !registers.get_zero() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bpl<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bpl<T: CpuRegisters>(operand: Word, registers: &mut T) -> Data {
     if !registers.get_negative() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bvs<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bvs<T: CpuRegisters>(operand: Word, registers: &mut T) -> Data {
     if registers.get_overflow() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
-pub fn bvc<T: CpuRegisters>(operand: Word, registers: &mut T) {
+pub fn bvc<T: CpuRegisters>(operand: Word, registers: &mut T) -> Data {
     if !registers.get_overflow() {
-        branch(registers, operand);
+        branch(registers, operand)
+    } else {
+        0
     }
 }
 
@@ -543,6 +571,170 @@ pub fn sed<T: CpuRegisters>(registers: &mut T) {
     registers.set_decimal(true);
 }
 
+pub fn nop<T: CpuRegisters, U: CpuBus>(operand: Word, _registers: &mut T, bus: &mut U) {
+    // Multi-byte NOPs read the operand and throw it away.
+    bus.read(operand);
+}
+
+pub fn lax<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = bus.read(operand);
+    registers
+        .set_A(fetched)
+        .set_X(fetched)
+        .update_negative_by(fetched)
+        .update_zero_by(fetched);
+}
+
+pub fn sax<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let data = registers.get_A() & registers.get_X();
+    bus.write(operand, data);
+}
+
+pub fn dcp<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = bus.read(operand);
+    bus.write(operand, fetched);
+    let data = fetched.wrapping_sub(1);
+    bus.write(operand, data);
+    cmp_imm(data as Word, registers);
+}
+
+pub fn isb<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = bus.read(operand);
+    bus.write(operand, fetched);
+    let data = fetched.wrapping_add(1);
+    bus.write(operand, data);
+    sbc_imm(data as Word, registers);
+}
+
+pub fn slo<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = bus.read(operand);
+    bus.write(operand, fetched);
+    let shifted = fetched << 1;
+    registers.set_carry(fetched & 0x80 == 0x80);
+    bus.write(operand, shifted);
+    ora_imm(shifted as Word, registers);
+}
+
+pub fn rla<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = bus.read(operand);
+    bus.write(operand, fetched);
+    let rotated = rotate_to_left(registers, fetched);
+    registers.set_carry(fetched & 0x80 == 0x80);
+    bus.write(operand, rotated);
+    and_imm(rotated as Word, registers);
+}
+
+pub fn sre<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = bus.read(operand);
+    bus.write(operand, fetched);
+    let shifted = fetched >> 1;
+    registers.set_carry(fetched & 0x01 == 0x01);
+    bus.write(operand, shifted);
+    eor_imm(shifted as Word, registers);
+}
+
+pub fn rra<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let fetched = bus.read(operand);
+    bus.write(operand, fetched);
+    let rotated = rotate_to_right(registers, fetched);
+    registers.set_carry(fetched & 0x01 == 0x01);
+    bus.write(operand, rotated);
+    adc_imm(rotated as Word, registers);
+}
+
+pub fn anc<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    and_imm(operand, registers);
+    let negative = registers.get_negative();
+    registers.set_carry(negative);
+}
+
+pub fn alr<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    and_imm(operand, registers);
+    lsr_acc(registers);
+}
+
+pub fn arr<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    let data = registers.get_A() & (operand as Data);
+    let rotated = rotate_to_right(registers, data);
+    registers
+        .set_carry(rotated & 0x40 == 0x40)
+        .set_overflow(((rotated >> 6) ^ (rotated >> 5)) & 0x01 == 0x01)
+        .update_negative_by(rotated)
+        .update_zero_by(rotated)
+        .set_A(rotated);
+}
+
+pub fn axs<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    let data = registers.get_A() & registers.get_X();
+    let computed = data.wrapping_sub(operand as Data);
+    registers
+        .set_carry(data >= operand as Data)
+        .update_negative_by(computed)
+        .update_zero_by(computed)
+        .set_X(computed);
+}
+
+// ANE and LXA mix in an analog constant which differs between chips.
+// 0xEE is used as the commonly observed value to keep them deterministic.
+const UNSTABLE_MAGIC: Data = 0xEE;
+
+pub fn ane<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    let computed = (registers.get_A() | UNSTABLE_MAGIC) & registers.get_X() & (operand as Data);
+    registers
+        .update_negative_by(computed)
+        .update_zero_by(computed)
+        .set_A(computed);
+}
+
+pub fn lxa<T: CpuRegisters>(operand: Word, registers: &mut T) {
+    let computed = (registers.get_A() | UNSTABLE_MAGIC) & (operand as Data);
+    registers
+        .update_negative_by(computed)
+        .update_zero_by(computed)
+        .set_A(computed)
+        .set_X(computed);
+}
+
+pub fn sha<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let data = registers.get_A() & registers.get_X();
+    let index = registers.get_Y();
+    store_and_high_byte(operand, index, data, bus);
+}
+
+pub fn shx<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let data = registers.get_X();
+    let index = registers.get_Y();
+    store_and_high_byte(operand, index, data, bus);
+}
+
+pub fn shy<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let data = registers.get_Y();
+    let index = registers.get_X();
+    store_and_high_byte(operand, index, data, bus);
+}
+
+pub fn tas<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let sp = registers.get_A() & registers.get_X();
+    registers.set_SP(sp);
+    let index = registers.get_Y();
+    store_and_high_byte(operand, index, sp, bus);
+}
+
+pub fn las<T: CpuRegisters, U: CpuBus>(operand: Word, registers: &mut T, bus: &mut U) {
+    let computed = bus.read(operand) & registers.get_SP();
+    registers
+        .set_A(computed)
+        .set_X(computed)
+        .set_SP(computed)
+        .update_negative_by(computed)
+        .update_zero_by(computed);
+}
+
+pub fn kil<T: CpuRegisters>(registers: &mut T) {
+    // The CPU jams with PC on the next byte and stops fetching until reset.
+    registers.set_halted(true);
+}
+
 fn rotate_to_right<T: CpuRegisters>(registers: &mut T, v: Data) -> Data {
     ((v >> 1) as Data | if registers.get_carry() { 0x80 } else { 0x00 }) as Data
 }
@@ -585,8 +777,28 @@ fn push_pc<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
     push(pc as u8, registers, bus);
 }
 
-fn branch<T: CpuRegisters>(registers: &mut T, addr: Addr) {
+// A taken branch costs one more cycle, and another one when it lands on a different page.
+fn branch<T: CpuRegisters>(registers: &mut T, addr: Addr) -> Data {
+    let is_page_crossed = (registers.get_PC() ^ addr) & 0xFF00 != 0;
     registers.set_PC(addr);
+    if is_page_crossed {
+        2
+    } else {
+        1
+    }
+}
+
+// SHA, SHX, SHY and TAS store the value ANDed with the high byte of the base address plus one.
+// When indexing crosses a page, that value also replaces the high byte of the target address.
+fn store_and_high_byte<U: CpuBus>(operand: Word, index: Data, data: Data, bus: &mut U) {
+    let base = operand.wrapping_sub(index as Word);
+    let data = data & ((base >> 8) as Data).wrapping_add(1);
+    let addr = if (base ^ operand) & 0xFF00 != 0 {
+        ((data as Word) << 8) | (operand & 0x00FF)
+    } else {
+        operand
+    };
+    bus.write(addr, data);
 }
 
 #[cfg(test)]
@@ -1045,4 +1257,118 @@ mod test {
         jmp(0x10, &mut reg);
         assert_eq!(reg.get_PC(), 0x10);
     }
+
+    #[test]
+    fn test_lax() {
+        let mut reg = Registers::new();
+        let mut bus = MockBus::new();
+        bus.mem[0x10] = 0x85;
+        lax(0x10, &mut reg, &mut bus);
+        assert_eq!(reg.get_A(), 0x85);
+        assert_eq!(reg.get_X(), 0x85);
+        assert!(reg.get_negative());
+    }
+
+    #[test]
+    fn test_sax() {
+        let mut reg = Registers::new();
+        let mut bus = MockBus::new();
+        reg.set_A(0xF0).set_X(0x3C);
+        sax(0x10, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0x10], 0x30);
+    }
+
+    #[test]
+    fn test_dcp() {
+        let mut reg = Registers::new();
+        let mut bus = MockBus::new();
+        reg.set_A(0x40);
+        bus.mem[0x10] = 0x41;
+        dcp(0x10, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0x10], 0x40);
+        assert!(reg.get_zero());
+        assert!(reg.get_carry());
+    }
+
+    #[test]
+    fn test_isb() {
+        let mut reg = Registers::new();
+        let mut bus = MockBus::new();
+        reg.set_A(0x10).set_carry(true);
+        bus.mem[0x10] = 0x04;
+        isb(0x10, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0x10], 0x05);
+        assert_eq!(reg.get_A(), 0x0B);
+    }
+
+    #[test]
+    fn test_slo() {
+        let mut reg = Registers::new();
+        let mut bus = MockBus::new();
+        reg.set_A(0x01);
+        bus.mem[0x10] = 0x81;
+        slo(0x10, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0x10], 0x02);
+        assert_eq!(reg.get_A(), 0x03);
+        assert!(reg.get_carry());
+    }
+
+    #[test]
+    fn test_rra() {
+        let mut reg = Registers::new();
+        let mut bus = MockBus::new();
+        reg.set_A(0x10).set_carry(false);
+        bus.mem[0x10] = 0x03;
+        rra(0x10, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0x10], 0x01);
+        assert_eq!(reg.get_A(), 0x12);
+    }
+
+    #[test]
+    fn test_arr() {
+        let mut reg = Registers::new();
+        reg.set_A(0xFF).set_carry(true);
+        arr(0xC0, &mut reg);
+        assert_eq!(reg.get_A(), 0xE0);
+        assert!(reg.get_carry());
+        assert!(!reg.get_overflow());
+    }
+
+    #[test]
+    fn test_axs() {
+        let mut reg = Registers::new();
+        reg.set_A(0x0F).set_X(0xFC);
+        axs(0x0D, &mut reg);
+        assert_eq!(reg.get_X(), 0xFF);
+        assert!(!reg.get_carry());
+    }
+
+    #[test]
+    fn test_shx_crossing_page() {
+        let mut reg = Registers::new();
+        let mut bus = MockBus::new();
+        reg.set_X(0x05).set_Y(0x20);
+        shx(0x0310, &mut reg, &mut bus);
+        assert_eq!(bus.mem[0x0110], 0x01);
+        assert_eq!(bus.mem[0x0310], 0x00);
+    }
+
+    #[test]
+    fn test_kil() {
+        let mut reg = Registers::new();
+        kil(&mut reg);
+        assert!(reg.is_halted());
+    }
+
+    #[test]
+    fn test_bne_cycles() {
+        let mut reg = Registers::new();
+        reg.set_PC(0x80F0).update_zero_by(0x00);
+        assert_eq!(bne(0x8100, &mut reg), 0);
+        assert_eq!(reg.get_PC(), 0x80F0);
+        reg.update_zero_by(0x01);
+        assert_eq!(bne(0x80F8, &mut reg), 1);
+        assert_eq!(bne(0x8100, &mut reg), 2);
+        assert_eq!(reg.get_PC(), 0x8100);
+    }
 }
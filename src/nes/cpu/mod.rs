@@ -1,35 +1,72 @@
 mod fetch;
 mod instructions;
 mod opecode;
+pub mod trace;
 
 use self::fetch::*;
 use self::instructions::*;
-use self::opecode::*;
+pub use self::opecode::*;
 use std::fmt::Debug;
 
 use super::bus::cpu_bus::CpuBus;
 use super::cpu_registers::CpuRegisters;
+use super::interrupts::Interrupts;
 use super::types::Data;
 
+const INTERRUPT_CYCLES: Data = 7;
+// A jammed CPU still lets the clock run so PPU and APU keep going.
+const HALTED_CYCLES: Data = 2;
+
 pub fn reset<T: CpuRegisters, U: CpuBus>(registers: &mut T, bus: &mut U) {
     let pc = bus.read_word(0xFFFC);
-    registers.set_PC(pc);
+    registers.set_PC(pc).set_halted(false);
 }
 
 pub fn run<T: CpuRegisters + Debug, U: CpuBus>(
     registers: &mut T,
     bus: &mut U,
-    nmi: &mut bool,
+    interrupts: &mut Interrupts,
 ) -> Data {
-    if *nmi {
+    run_with_hook(registers, bus, interrupts, |_, _, _| ())
+}
+
+// The hook is called with the registers and the decoded opecode
+// right before the instruction is fetched, PC still points to the opecode.
+pub fn run_with_hook<T, U, F>(
+    registers: &mut T,
+    bus: &mut U,
+    interrupts: &mut Interrupts,
+    mut hook: F,
+) -> Data
+where
+    T: CpuRegisters + Debug,
+    U: CpuBus,
+    F: FnMut(&T, &Opecode, &mut U),
+{
+    if registers.is_halted() {
+        return HALTED_CYCLES;
+    }
+    let mut cycle = 0;
+    if interrupts.is_nmi_asserted() {
         process_nmi(registers, bus);
-        *nmi = false;
+        interrupts.deassert_nmi();
+        cycle += INTERRUPT_CYCLES;
+    } else if interrupts.is_irq_pending() {
+        process_irq(registers, bus);
+        cycle += INTERRUPT_CYCLES;
     }
-    let code = fetch(registers, bus);
-    let ref map = opecode::MAP;
-    let code = &*map.get(&code).unwrap();
-    let operand = fetch_operand(&code, registers, bus);
+    let map = &*opecode::MAP;
+    let code = &map[&bus.read(registers.get_PC())];
+    hook(registers, code, bus);
+    registers.inc_PC();
+    let (operand, page_crossing_cycle) = fetch_operand(code, registers, bus);
+    let mut additional_cycle = if has_page_crossing_penalty(&code.name) {
+        page_crossing_cycle
+    } else {
+        0
+    };
     // println!("opecode = {}, {:?} pc = {:x}, operand = {:x}", &_code, code.name, &registers.get_PC(), operand);
+    let interrupt = registers.get_interrupt();
     match code.name {
         Instruction::LDA if code.mode == Addressing::Immediate => lda_imm(operand, registers),
         Instruction::LDA => lda(operand, registers, bus),
@@ -86,31 +123,71 @@ pub fn run<T: CpuRegisters + Debug, U: CpuBus>(
         Instruction::CLV => clv(registers),
         Instruction::SEC => sec(registers),
         Instruction::SEI => sei(registers),
-        Instruction::NOP => (),
+        Instruction::NOP if code.mode == Addressing::Implied => (),
+        Instruction::NOP if code.mode == Addressing::Immediate => (),
+        Instruction::NOP => nop(operand, registers, bus),
         Instruction::BRK => brk(registers, bus),
         Instruction::JSR => jsr(operand, registers, bus),
         Instruction::JMP => jmp(operand, registers),
         Instruction::RTI => rti(registers, bus),
         Instruction::RTS => rts(registers, bus),
-        Instruction::BCC => bcc(operand, registers),
-        Instruction::BPL => bpl(operand, registers),
-        Instruction::BMI => bmi(operand, registers),
-        Instruction::BVC => bvc(operand, registers),
-        Instruction::BVS => bvs(operand, registers),
-        Instruction::BCS => bcs(operand, registers),
-        Instruction::BNE => bne(operand, registers),
-        Instruction::BEQ => beq(operand, registers),
+        Instruction::BCC => additional_cycle += bcc(operand, registers),
+        Instruction::BPL => additional_cycle += bpl(operand, registers),
+        Instruction::BMI => additional_cycle += bmi(operand, registers),
+        Instruction::BVC => additional_cycle += bvc(operand, registers),
+        Instruction::BVS => additional_cycle += bvs(operand, registers),
+        Instruction::BCS => additional_cycle += bcs(operand, registers),
+        Instruction::BNE => additional_cycle += bne(operand, registers),
+        Instruction::BEQ => additional_cycle += beq(operand, registers),
         Instruction::SED => sed(registers),
         Instruction::CLD => cld(registers),
-        Instruction::LAX => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::SAX => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::DCP => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::ISB => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::SLO => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::RLA => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::SRE => println!("{}", "TODO:Undocumented instruction"),
-        Instruction::RRA => println!("{}", "TODO:Undocumented instruction"),
-        _ => panic!("{}", "Undefined opecode detected."),
+        Instruction::LAX => lax(operand, registers, bus),
+        Instruction::SAX => sax(operand, registers, bus),
+        Instruction::DCP => dcp(operand, registers, bus),
+        Instruction::ISB => isb(operand, registers, bus),
+        Instruction::SLO => slo(operand, registers, bus),
+        Instruction::RLA => rla(operand, registers, bus),
+        Instruction::SRE => sre(operand, registers, bus),
+        Instruction::RRA => rra(operand, registers, bus),
+        Instruction::ANC => anc(operand, registers),
+        Instruction::ALR => alr(operand, registers),
+        Instruction::ARR => arr(operand, registers),
+        Instruction::AXS => axs(operand, registers),
+        Instruction::ANE => ane(operand, registers),
+        Instruction::LXA => lxa(operand, registers),
+        Instruction::SHA => sha(operand, registers, bus),
+        Instruction::SHX => shx(operand, registers, bus),
+        Instruction::SHY => shy(operand, registers, bus),
+        Instruction::TAS => tas(operand, registers, bus),
+        Instruction::LAS => las(operand, registers, bus),
+        Instruction::KIL => kil(registers),
     }
-    code.cycle
+    // CLI, SEI and PLP change the I flag after interrupts are polled.
+    let is_delayed = matches!(code.name, Instruction::CLI | Instruction::SEI | Instruction::PLP);
+    interrupts.mask_irq(if is_delayed {
+        interrupt
+    } else {
+        registers.get_interrupt()
+    });
+    cycle + code.cycle + additional_cycle
+}
+
+// Only instructions that just read memory finish early when indexing stays on the same page.
+// Stores and read-modify-write instructions always take the extra cycle.
+fn has_page_crossing_penalty(name: &Instruction) -> bool {
+    matches!(
+        *name,
+        Instruction::LDA
+            | Instruction::LDX
+            | Instruction::LDY
+            | Instruction::ADC
+            | Instruction::SBC
+            | Instruction::CMP
+            | Instruction::AND
+            | Instruction::EOR
+            | Instruction::ORA
+            | Instruction::NOP
+            | Instruction::LAX
+            | Instruction::LAS
+    )
 }
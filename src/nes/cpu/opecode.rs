@@ -76,6 +76,18 @@ pub enum Instruction {
     RLA,
     SRE,
     RRA,
+    ANC,
+    ALR,
+    ARR,
+    AXS,
+    ANE,
+    LXA,
+    SHA,
+    SHX,
+    SHY,
+    TAS,
+    LAS,
+    KIL,
 }
 
 #[derive(Debug, PartialEq)]
@@ -101,16 +113,22 @@ lazy_static! {
 
             #[cfg_attr(rustfmt, rustfmt_skip)]
             let cycles: Vec<u8> =
-                vec![7, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7,
-                     4, 4, 6, 7, 6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6,
-                     2, 4, 2, 7, 4, 4, 6, 7, 6, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6, 2, 5, 2, 8,
-                     4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 6, 7, 6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6,
-                     2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 6, 7, 2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2,
-                     4, 4, 4, 4, 2, 6, 2, 6, 4, 4, 4, 4, 2, 4, 2, 5, 5, 4, 5, 5, 2, 6, 2, 6, 3, 3, 3, 3,
-                     2, 2, 2, 2, 4, 4, 4, 4, 2, 5, 2, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4, 2, 6, 2, 8,
-                     3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
-                     2, 6, 3, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, 2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7,
-                     4, 4, 7, 7];            
+                vec![7, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6,
+                     2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+                     6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6,
+                     2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+                     6, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6,
+                     2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+                     6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6,
+                     2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+                     2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+                     2, 6, 2, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5,
+                     2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+                     2, 5, 2, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4,
+                     2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+                     2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+                     2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+                     2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7];
             let mut m = HashMap::new();
             m.insert(0xA9, Opecode { name: Instruction::LDA, mode: Addressing::Immediate, cycle: cycles[0xA9] });
             m.insert(0xA5, Opecode { name: Instruction::LDA, mode: Addressing::ZeroPage, cycle: cycles[0xA5] });
@@ -269,39 +287,39 @@ lazy_static! {
             m.insert(0x7A, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x7A] });
             m.insert(0xDA, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xDA] });
             m.insert(0xFA, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xFA] });
-            m.insert(0x02, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x02] });
-            m.insert(0x12, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x12] });
-            m.insert(0x22, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x22] });
-            m.insert(0x32, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x32] });
-            m.insert(0x42, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x42] });
-            m.insert(0x52, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x52] });
-            m.insert(0x62, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x62] });
-            m.insert(0x72, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x72] });
-            m.insert(0x92, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x92] });
-            m.insert(0xB2, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xB2] });
-            m.insert(0xD2, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xD2] });
-            m.insert(0xF2, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xF2] });
-            m.insert(0x80, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x80] });
-            m.insert(0x82, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x82] });
-            m.insert(0x89, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x89] });
-            m.insert(0xC2, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xC2] });
-            m.insert(0xE2, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xE2] });
-            m.insert(0x04, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x04] });
-            m.insert(0x44, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x44] });
-            m.insert(0x64, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x64] });
-            m.insert(0x14, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x14] });
-            m.insert(0x34, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x34] });
-            m.insert(0x54, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x54] });
-            m.insert(0x74, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x74] });
-            m.insert(0xD4, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xD4] });
-            m.insert(0xF4, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xF4] });
-            m.insert(0x0C, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x0C] });
-            m.insert(0x1C, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x1C] });
-            m.insert(0x3C, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x3C] });
-            m.insert(0x5C, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x5C] });
-            m.insert(0x7C, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0x7C] });
-            m.insert(0xDC, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xDC] });
-            m.insert(0xFC, Opecode { name: Instruction::NOP, mode: Addressing::Implied, cycle: cycles[0xFC] });
+            m.insert(0x02, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0x02] });
+            m.insert(0x12, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0x12] });
+            m.insert(0x22, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0x22] });
+            m.insert(0x32, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0x32] });
+            m.insert(0x42, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0x42] });
+            m.insert(0x52, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0x52] });
+            m.insert(0x62, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0x62] });
+            m.insert(0x72, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0x72] });
+            m.insert(0x92, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0x92] });
+            m.insert(0xB2, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0xB2] });
+            m.insert(0xD2, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0xD2] });
+            m.insert(0xF2, Opecode { name: Instruction::KIL, mode: Addressing::Implied, cycle: cycles[0xF2] });
+            m.insert(0x80, Opecode { name: Instruction::NOP, mode: Addressing::Immediate, cycle: cycles[0x80] });
+            m.insert(0x82, Opecode { name: Instruction::NOP, mode: Addressing::Immediate, cycle: cycles[0x82] });
+            m.insert(0x89, Opecode { name: Instruction::NOP, mode: Addressing::Immediate, cycle: cycles[0x89] });
+            m.insert(0xC2, Opecode { name: Instruction::NOP, mode: Addressing::Immediate, cycle: cycles[0xC2] });
+            m.insert(0xE2, Opecode { name: Instruction::NOP, mode: Addressing::Immediate, cycle: cycles[0xE2] });
+            m.insert(0x04, Opecode { name: Instruction::NOP, mode: Addressing::ZeroPage, cycle: cycles[0x04] });
+            m.insert(0x44, Opecode { name: Instruction::NOP, mode: Addressing::ZeroPage, cycle: cycles[0x44] });
+            m.insert(0x64, Opecode { name: Instruction::NOP, mode: Addressing::ZeroPage, cycle: cycles[0x64] });
+            m.insert(0x14, Opecode { name: Instruction::NOP, mode: Addressing::ZeroPageX, cycle: cycles[0x14] });
+            m.insert(0x34, Opecode { name: Instruction::NOP, mode: Addressing::ZeroPageX, cycle: cycles[0x34] });
+            m.insert(0x54, Opecode { name: Instruction::NOP, mode: Addressing::ZeroPageX, cycle: cycles[0x54] });
+            m.insert(0x74, Opecode { name: Instruction::NOP, mode: Addressing::ZeroPageX, cycle: cycles[0x74] });
+            m.insert(0xD4, Opecode { name: Instruction::NOP, mode: Addressing::ZeroPageX, cycle: cycles[0xD4] });
+            m.insert(0xF4, Opecode { name: Instruction::NOP, mode: Addressing::ZeroPageX, cycle: cycles[0xF4] });
+            m.insert(0x0C, Opecode { name: Instruction::NOP, mode: Addressing::Absolute, cycle: cycles[0x0C] });
+            m.insert(0x1C, Opecode { name: Instruction::NOP, mode: Addressing::AbsoluteX, cycle: cycles[0x1C] });
+            m.insert(0x3C, Opecode { name: Instruction::NOP, mode: Addressing::AbsoluteX, cycle: cycles[0x3C] });
+            m.insert(0x5C, Opecode { name: Instruction::NOP, mode: Addressing::AbsoluteX, cycle: cycles[0x5C] });
+            m.insert(0x7C, Opecode { name: Instruction::NOP, mode: Addressing::AbsoluteX, cycle: cycles[0x7C] });
+            m.insert(0xDC, Opecode { name: Instruction::NOP, mode: Addressing::AbsoluteX, cycle: cycles[0xDC] });
+            m.insert(0xFC, Opecode { name: Instruction::NOP, mode: Addressing::AbsoluteX, cycle: cycles[0xFC] });
             m.insert(0xA7, Opecode { name: Instruction::LAX, mode: Addressing::ZeroPage, cycle: cycles[0xA7] });
             m.insert(0xB7, Opecode { name: Instruction::LAX, mode: Addressing::ZeroPageY, cycle: cycles[0xB7] });
             m.insert(0xAF, Opecode { name: Instruction::LAX, mode: Addressing::Absolute, cycle: cycles[0xAF] });
@@ -317,14 +335,14 @@ lazy_static! {
             m.insert(0xD7, Opecode { name: Instruction::DCP, mode: Addressing::ZeroPageX, cycle: cycles[0xD7] });
             m.insert(0xCF, Opecode { name: Instruction::DCP, mode: Addressing::Absolute, cycle: cycles[0xCF] });
             m.insert(0xDF, Opecode { name: Instruction::DCP, mode: Addressing::AbsoluteX, cycle: cycles[0xDF] });
-            m.insert(0xDB, Opecode { name: Instruction::DCP, mode: Addressing::AbsoluteY, cycle: cycles[0xD8] });
+            m.insert(0xDB, Opecode { name: Instruction::DCP, mode: Addressing::AbsoluteY, cycle: cycles[0xDB] });
             m.insert(0xC3, Opecode { name: Instruction::DCP, mode: Addressing::PreIndexedIndirect, cycle: cycles[0xC3] });
             m.insert(0xD3, Opecode { name: Instruction::DCP, mode: Addressing::PostIndexedIndirect, cycle: cycles[0xD3] });
             m.insert(0xE7, Opecode { name: Instruction::ISB, mode: Addressing::ZeroPage, cycle: cycles[0xE7] });
             m.insert(0xF7, Opecode { name: Instruction::ISB, mode: Addressing::ZeroPageX, cycle: cycles[0xF7] });
             m.insert(0xEF, Opecode { name: Instruction::ISB, mode: Addressing::Absolute, cycle: cycles[0xEF] });
             m.insert(0xFF, Opecode { name: Instruction::ISB, mode: Addressing::AbsoluteX, cycle: cycles[0xFF] });
-            m.insert(0xFB, Opecode { name: Instruction::ISB, mode: Addressing::AbsoluteY, cycle: cycles[0xF8] });
+            m.insert(0xFB, Opecode { name: Instruction::ISB, mode: Addressing::AbsoluteY, cycle: cycles[0xFB] });
             m.insert(0xE3, Opecode { name: Instruction::ISB, mode: Addressing::PreIndexedIndirect, cycle: cycles[0xE3] });
             m.insert(0xF3, Opecode { name: Instruction::ISB, mode: Addressing::PostIndexedIndirect, cycle: cycles[0xF3] });
             m.insert(0x07, Opecode { name: Instruction::SLO, mode: Addressing::ZeroPage, cycle: cycles[0x07] });
@@ -340,21 +358,34 @@ lazy_static! {
             m.insert(0x3F, Opecode { name: Instruction::RLA, mode: Addressing::AbsoluteX, cycle: cycles[0x3F] });
             m.insert(0x3B, Opecode { name: Instruction::RLA, mode: Addressing::AbsoluteY, cycle: cycles[0x3B] });
             m.insert(0x23, Opecode { name: Instruction::RLA, mode: Addressing::PreIndexedIndirect, cycle: cycles[0x23] });
-            m.insert(0x33, Opecode { name: Instruction::RLA, mode: Addressing::PostIndexedIndirect, cycle: cycles[0x33] }, );
+            m.insert(0x33, Opecode { name: Instruction::RLA, mode: Addressing::PostIndexedIndirect, cycle: cycles[0x33] });
             m.insert(0x47, Opecode { name: Instruction::SRE, mode: Addressing::ZeroPage, cycle: cycles[0x47] });
             m.insert(0x57, Opecode { name: Instruction::SRE, mode: Addressing::ZeroPageX, cycle: cycles[0x57] });
             m.insert(0x4F, Opecode { name: Instruction::SRE, mode: Addressing::Absolute, cycle: cycles[0x4F] });
             m.insert(0x5F, Opecode { name: Instruction::SRE, mode: Addressing::AbsoluteX, cycle: cycles[0x5F] });
             m.insert(0x5B, Opecode { name: Instruction::SRE, mode: Addressing::AbsoluteY, cycle: cycles[0x5B] });
             m.insert(0x43, Opecode { name: Instruction::SRE, mode: Addressing::PreIndexedIndirect, cycle: cycles[0x43] });
-            m.insert(0x53, Opecode { name: Instruction::SRE, mode: Addressing::PostIndexedIndirect, cycle: cycles[0x53] }, );
+            m.insert(0x53, Opecode { name: Instruction::SRE, mode: Addressing::PostIndexedIndirect, cycle: cycles[0x53] });
             m.insert(0x67, Opecode { name: Instruction::RRA, mode: Addressing::ZeroPage, cycle: cycles[0x67] });
             m.insert(0x77, Opecode { name: Instruction::RRA, mode: Addressing::ZeroPageX, cycle: cycles[0x77] });
             m.insert(0x6F, Opecode { name: Instruction::RRA, mode: Addressing::Absolute, cycle: cycles[0x6F] });
             m.insert(0x7F, Opecode { name: Instruction::RRA, mode: Addressing::AbsoluteX, cycle: cycles[0x7F] });
             m.insert(0x7B, Opecode { name: Instruction::RRA, mode: Addressing::AbsoluteY, cycle: cycles[0x7B] });
             m.insert(0x63, Opecode { name: Instruction::RRA, mode: Addressing::PreIndexedIndirect, cycle: cycles[0x63] });
-            m.insert(0x73, Opecode { name: Instruction::RRA, mode: Addressing::PostIndexedIndirect, cycle: cycles[0x73] });            
+            m.insert(0x73, Opecode { name: Instruction::RRA, mode: Addressing::PostIndexedIndirect, cycle: cycles[0x73] });
+            m.insert(0x0B, Opecode { name: Instruction::ANC, mode: Addressing::Immediate, cycle: cycles[0x0B] });
+            m.insert(0x2B, Opecode { name: Instruction::ANC, mode: Addressing::Immediate, cycle: cycles[0x2B] });
+            m.insert(0x4B, Opecode { name: Instruction::ALR, mode: Addressing::Immediate, cycle: cycles[0x4B] });
+            m.insert(0x6B, Opecode { name: Instruction::ARR, mode: Addressing::Immediate, cycle: cycles[0x6B] });
+            m.insert(0xCB, Opecode { name: Instruction::AXS, mode: Addressing::Immediate, cycle: cycles[0xCB] });
+            m.insert(0x8B, Opecode { name: Instruction::ANE, mode: Addressing::Immediate, cycle: cycles[0x8B] });
+            m.insert(0xAB, Opecode { name: Instruction::LXA, mode: Addressing::Immediate, cycle: cycles[0xAB] });
+            m.insert(0x93, Opecode { name: Instruction::SHA, mode: Addressing::PostIndexedIndirect, cycle: cycles[0x93] });
+            m.insert(0x9F, Opecode { name: Instruction::SHA, mode: Addressing::AbsoluteY, cycle: cycles[0x9F] });
+            m.insert(0x9E, Opecode { name: Instruction::SHX, mode: Addressing::AbsoluteY, cycle: cycles[0x9E] });
+            m.insert(0x9C, Opecode { name: Instruction::SHY, mode: Addressing::AbsoluteX, cycle: cycles[0x9C] });
+            m.insert(0x9B, Opecode { name: Instruction::TAS, mode: Addressing::AbsoluteY, cycle: cycles[0x9B] });
+            m.insert(0xBB, Opecode { name: Instruction::LAS, mode: Addressing::AbsoluteY, cycle: cycles[0xBB] });
             m
         };        
     }
@@ -0,0 +1,158 @@
+use super::opecode::*;
+use super::super::cpu_registers::CpuRegisters;
+use super::super::types::{Addr, Data, Word};
+
+// Formats a Nintendulator style trace line, the same layout as nestest.log.
+//
+// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+//
+// Unofficial opecodes are marked with `*` in front of the mnemonic.
+// P is shown without the break flag, which only exists on the stack.
+// `peek` reads the operand values to show, so it should be free from read side effects.
+// PPU shows `line` and `dot` of the PPU and CYC the CPU cycles since reset.
+pub fn format_line<T: CpuRegisters, F: Fn(Addr) -> Data>(
+    registers: &T,
+    code: &Opecode,
+    peek: F,
+    (line, dot): (usize, usize),
+    cycles: usize,
+) -> String {
+    let pc = registers.get_PC();
+    let opecode = peek(pc);
+    let bytes = (0..(get_operand_length(&code.mode) + 1))
+        .map(|i| format!("{:02X}", peek(pc.wrapping_add(i))))
+        .collect::<Vec<String>>()
+        .join(" ");
+    let prefix = if is_unofficial(opecode, &code.name) {
+        '*'
+    } else {
+        ' '
+    };
+    format!(
+        "{:04X}  {:<9}{}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        pc,
+        bytes,
+        prefix,
+        disassemble(registers, code, &peek),
+        registers.get_A(),
+        registers.get_X(),
+        registers.get_Y(),
+        registers.get_P() & !0x10,
+        registers.get_SP(),
+        line,
+        dot,
+        cycles
+    )
+}
+
+fn get_operand_length(mode: &Addressing) -> Word {
+    match *mode {
+        Addressing::Implied | Addressing::Accumulator => 0,
+        Addressing::Absolute
+        | Addressing::AbsoluteX
+        | Addressing::AbsoluteY
+        | Addressing::IndirectAbsolute => 2,
+        _ => 1,
+    }
+}
+
+fn is_unofficial(opecode: Data, name: &Instruction) -> bool {
+    match *name {
+        Instruction::NOP => opecode != 0xEA,
+        Instruction::SBC => opecode == 0xEB,
+        Instruction::LAX
+        | Instruction::SAX
+        | Instruction::DCP
+        | Instruction::ISB
+        | Instruction::SLO
+        | Instruction::RLA
+        | Instruction::SRE
+        | Instruction::RRA
+        | Instruction::ANC
+        | Instruction::ALR
+        | Instruction::ARR
+        | Instruction::AXS
+        | Instruction::ANE
+        | Instruction::LXA
+        | Instruction::SHA
+        | Instruction::SHX
+        | Instruction::SHY
+        | Instruction::TAS
+        | Instruction::LAS
+        | Instruction::KIL => true,
+        _ => false,
+    }
+}
+
+fn disassemble<T: CpuRegisters, F: Fn(Addr) -> Data>(registers: &T, code: &Opecode, peek: &F) -> String {
+    let pc = registers.get_PC();
+    let lower = peek(pc.wrapping_add(1));
+    let upper = peek(pc.wrapping_add(2));
+    let word = (upper as Word) << 8 | lower as Word;
+    let name = format!("{:?}", code.name);
+    let operand = match code.mode {
+        Addressing::Implied => return name,
+        Addressing::Accumulator => "A".to_string(),
+        Addressing::Immediate => format!("#${:02X}", lower),
+        Addressing::Relative => {
+            let offset = lower as i8 as Word;
+            format!("${:04X}", pc.wrapping_add(2).wrapping_add(offset))
+        }
+        Addressing::ZeroPage => format!("${:02X} = {:02X}", lower, peek(lower as Addr)),
+        Addressing::ZeroPageX => {
+            let addr = lower.wrapping_add(registers.get_X());
+            format!("${:02X},X @ {:02X} = {:02X}", lower, addr, peek(addr as Addr))
+        }
+        Addressing::ZeroPageY => {
+            let addr = lower.wrapping_add(registers.get_Y());
+            format!("${:02X},Y @ {:02X} = {:02X}", lower, addr, peek(addr as Addr))
+        }
+        Addressing::Absolute => match code.name {
+            Instruction::JMP | Instruction::JSR => format!("${:04X}", word),
+            _ => format!("${:04X} = {:02X}", word, peek(word)),
+        },
+        Addressing::AbsoluteX => {
+            let addr = word.wrapping_add(registers.get_X() as Word);
+            format!("${:04X},X @ {:04X} = {:02X}", word, addr, peek(addr))
+        }
+        Addressing::AbsoluteY => {
+            let addr = word.wrapping_add(registers.get_Y() as Word);
+            format!("${:04X},Y @ {:04X} = {:02X}", word, addr, peek(addr))
+        }
+        Addressing::IndirectAbsolute => {
+            // The upper byte is fetched without carrying into the page.
+            let upper_addr = (word & 0xFF00) | (word.wrapping_add(1) & 0x00FF);
+            let addr = (peek(upper_addr) as Word) << 8 | peek(word) as Word;
+            format!("(${:04X}) = {:04X}", word, addr)
+        }
+        Addressing::PreIndexedIndirect => {
+            let pointer = lower.wrapping_add(registers.get_X());
+            let addr = read_zeropage_word(pointer, peek);
+            format!(
+                "(${:02X},X) @ {:02X} = {:04X} = {:02X}",
+                lower,
+                pointer,
+                addr,
+                peek(addr)
+            )
+        }
+        Addressing::PostIndexedIndirect => {
+            let base = read_zeropage_word(lower, peek);
+            let addr = base.wrapping_add(registers.get_Y() as Word);
+            format!(
+                "(${:02X}),Y = {:04X} @ {:04X} = {:02X}",
+                lower,
+                base,
+                addr,
+                peek(addr)
+            )
+        }
+    };
+    format!("{} {}", name, operand)
+}
+
+fn read_zeropage_word<F: Fn(Addr) -> Data>(pointer: Data, peek: &F) -> Word {
+    let lower = peek(pointer as Addr) as Word;
+    let upper = peek(pointer.wrapping_add(1) as Addr) as Word;
+    upper << 8 | lower
+}
@@ -1,4 +1,5 @@
 use super::helper::*;
+use super::state::{Snapshot, StateReader, StateWriter};
 
 #[derive(Debug)]
 struct Status {
@@ -21,6 +22,8 @@ pub struct Registers {
     SP: u8,
     PC: u16,
     P: Status,
+    // Set by KIL, only reset can bring the CPU back.
+    is_halted: bool,
 }
 
 #[allow(non_snake_case)]
@@ -91,7 +94,9 @@ pub trait CpuRegisters {
 
     fn inc_PC(&mut self) -> &mut Self;
 
-    fn dec_PC(&mut self) -> &mut Self;
+    fn is_halted(&self) -> bool;
+
+    fn set_halted(&mut self, v: bool) -> &mut Self;
 }
 
 impl Registers {
@@ -112,10 +117,34 @@ impl Registers {
                 zero: false,
                 carry: false,
             },
+            is_halted: false,
         }
     }
 }
 
+impl Snapshot for Registers {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.A);
+        writer.write_u8(self.X);
+        writer.write_u8(self.Y);
+        writer.write_u8(self.SP);
+        writer.write_u16(self.PC);
+        writer.write_u8(self.get_P());
+        writer.write_bool(self.is_halted);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.A = reader.read_u8();
+        self.X = reader.read_u8();
+        self.Y = reader.read_u8();
+        self.SP = reader.read_u8();
+        self.PC = reader.read_u16();
+        let p = reader.read_u8();
+        self.set_P(p);
+        self.is_halted = reader.read_bool();
+    }
+}
+
 #[allow(non_snake_case)]
 impl CpuRegisters for Registers {
     fn get_PC(&self) -> u16 {
@@ -265,22 +294,26 @@ impl CpuRegisters for Registers {
     }
 
     fn inc_SP(&mut self) -> &mut Self {
-        self.SP += 1;
+        self.SP = self.SP.wrapping_add(1);
         self
     }
 
     fn dec_SP(&mut self) -> &mut Self {
-        self.SP -= 1;
+        self.SP = self.SP.wrapping_sub(1);
         self
     }
 
     fn inc_PC(&mut self) -> &mut Self {
-        self.PC += 1;
+        self.PC = self.PC.wrapping_add(1);
         self
     }
 
-    fn dec_PC(&mut self) -> &mut Self {
-        self.PC -= 1;
+    fn is_halted(&self) -> bool {
+        self.is_halted
+    }
+
+    fn set_halted(&mut self, v: bool) -> &mut Self {
+        self.is_halted = v;
         self
     }
 }
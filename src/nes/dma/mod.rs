@@ -1,6 +1,7 @@
 use super::types::{Data};
 use nes::ram::Ram;
 use nes::ppu::Ppu;
+use nes::state::{Snapshot, StateReader, StateWriter};
 
 #[derive(Debug)]
 pub struct Dma {
@@ -33,3 +34,15 @@ impl Dma {
         self.should_run = false;
     }
 }
+
+impl Snapshot for Dma {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.register);
+        writer.write_bool(self.should_run);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.register = reader.read_u8();
+        self.should_run = reader.read_bool();
+    }
+}
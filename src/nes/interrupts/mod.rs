@@ -0,0 +1,108 @@
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::Data;
+
+#[derive(Debug, Clone, Copy)]
+pub enum IrqSource {
+    FrameCounter = 0x01,
+    Dmc = 0x02,
+    Mapper = 0x04,
+}
+
+// Interrupt lines shared by the devices and polled by the CPU.
+// IRQ is level triggered, so it stays asserted while any source holds it.
+#[derive(Debug)]
+pub struct Interrupts {
+    nmi: bool,
+    irq: Data,
+    // The CPU polls interrupts before the last cycle of each instruction,
+    // so the I flag changed by CLI, SEI and PLP takes effect one instruction late.
+    is_irq_masked: bool,
+}
+
+impl Interrupts {
+    pub fn new() -> Self {
+        Interrupts {
+            nmi: false,
+            irq: 0,
+            is_irq_masked: true,
+        }
+    }
+
+    pub fn assert_nmi(&mut self) {
+        self.nmi = true;
+    }
+
+    pub fn deassert_nmi(&mut self) {
+        self.nmi = false;
+    }
+
+    pub fn is_nmi_asserted(&self) -> bool {
+        self.nmi
+    }
+
+    pub fn assert_irq(&mut self, source: IrqSource) {
+        self.irq |= source as Data;
+    }
+
+    pub fn deassert_irq(&mut self, source: IrqSource) {
+        self.irq &= !(source as Data);
+    }
+
+    #[cfg(test)]
+    pub fn is_irq_asserted_by(&self, source: IrqSource) -> bool {
+        self.irq & source as Data != 0
+    }
+
+    pub fn is_irq_asserted(&self) -> bool {
+        self.irq != 0
+    }
+
+    pub fn mask_irq(&mut self, is_masked: bool) {
+        self.is_irq_masked = is_masked;
+    }
+
+    pub fn is_irq_pending(&self) -> bool {
+        self.is_irq_asserted() && !self.is_irq_masked
+    }
+}
+
+impl Default for Interrupts {
+    fn default() -> Self {
+        Interrupts::new()
+    }
+}
+
+impl Snapshot for Interrupts {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.nmi);
+        writer.write_u8(self.irq);
+        writer.write_bool(self.is_irq_masked);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.nmi = reader.read_bool();
+        self.irq = reader.read_u8();
+        self.is_irq_masked = reader.read_bool();
+    }
+}
+
+#[test]
+fn keep_irq_while_any_source_asserted() {
+    let mut interrupts = Interrupts::new();
+    interrupts.assert_irq(IrqSource::FrameCounter);
+    interrupts.assert_irq(IrqSource::Mapper);
+    interrupts.deassert_irq(IrqSource::FrameCounter);
+    assert!(interrupts.is_irq_asserted());
+    assert!(interrupts.is_irq_asserted_by(IrqSource::Mapper));
+    interrupts.deassert_irq(IrqSource::Mapper);
+    assert!(!interrupts.is_irq_asserted());
+}
+
+#[test]
+fn hold_irq_while_masked() {
+    let mut interrupts = Interrupts::new();
+    interrupts.assert_irq(IrqSource::Dmc);
+    assert!(!interrupts.is_irq_pending());
+    interrupts.mask_irq(false);
+    assert!(interrupts.is_irq_pending());
+}
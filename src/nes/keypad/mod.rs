@@ -1,4 +1,5 @@
 use super::types::{Data, Addr};
+use nes::state::{Snapshot, StateReader, StateWriter};
 
 #[derive(Debug)]
 pub struct Keypad {
@@ -25,7 +26,7 @@ impl Keypad {
     pub fn write(&mut self, data: Data) {
         if data & 0x01 == 0x01 {
             self.reset = true;
-        } else if self.reset && data & 0x00 == 0x00 {
+        } else if self.reset {
             self.reset = false;
             self.addr = 0;
             self.register = self.buffer;
@@ -33,9 +34,29 @@ impl Keypad {
     }
 
     pub fn read(&mut self) -> u8 {
+        // Official controllers return 1 after all of the 8 buttons are read.
+        if self.addr >= 8 {
+            return 1;
+        }
         let v = (0x01 << self.addr) as u8;
         let ret = ((self.register & v) >> self.addr) as u8;
         self.addr += 1;
         ret
     }
 }
+
+impl Snapshot for Keypad {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.addr);
+        writer.write_bool(self.reset);
+        writer.write_u8(self.register);
+        writer.write_u8(self.buffer);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.addr = reader.read_u16();
+        self.reset = reader.read_bool();
+        self.register = reader.read_u8();
+        self.buffer = reader.read_u8();
+    }
+}
@@ -0,0 +1,108 @@
+use super::{mirror_down_program_addr, CharacterMemory, Mapper, Mirroring, ProgramRam};
+use nes::parser::Cassette;
+use nes::rom::Rom;
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::{Addr, Data};
+
+const CHARACTER_BANK_SIZE: usize = 0x2000;
+
+// Mapper3
+// Any write to 0x8000-0xFFFF selects the 8KiB character bank.
+#[derive(Debug)]
+pub struct Cnrom {
+    program_rom: Rom,
+    character_memory: CharacterMemory,
+    program_ram: ProgramRam,
+    mirroring: Mirroring,
+    bank: usize,
+}
+
+impl Cnrom {
+    pub fn new(cassette: Cassette) -> Self {
+        Cnrom {
+            program_rom: Rom::new(cassette.program_rom),
+            character_memory: CharacterMemory::new(cassette.character_ram),
+            program_ram: ProgramRam::new(cassette.program_ram_size, cassette.has_battery),
+            mirroring: cassette.mirroring,
+            bank: 0,
+        }
+    }
+
+    fn get_bank_count(&self) -> usize {
+        self.character_memory.size() / CHARACTER_BANK_SIZE
+    }
+}
+
+impl Snapshot for Cnrom {
+    fn save(&self, writer: &mut StateWriter) {
+        self.character_memory.save(writer);
+        self.program_ram.save(writer);
+        writer.write_usize(self.bank);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.character_memory.load(reader);
+        self.program_ram.load(reader);
+        self.bank = reader.read_usize();
+    }
+}
+
+impl Mapper for Cnrom {
+    fn read(&self, addr: Addr) -> Data {
+        match addr {
+            0x6000..=0x7FFF => self.program_ram.read(addr),
+            0x8000..=0xFFFF => self
+                .program_rom
+                .read(mirror_down_program_addr(addr, self.program_rom.size())),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Data) {
+        match addr {
+            0x6000..=0x7FFF => self.program_ram.write(addr, data),
+            0x8000..=0xFFFF => self.bank = data as usize % self.get_bank_count(),
+            _ => (),
+        }
+    }
+
+    fn read_character(&self, addr: Addr) -> Data {
+        self.character_memory
+            .read(self.bank * CHARACTER_BANK_SIZE + addr as usize)
+    }
+
+    fn write_character(&mut self, addr: Addr, data: Data) {
+        let addr = self.bank * CHARACTER_BANK_SIZE + addr as usize;
+        self.character_memory.write(addr, data);
+    }
+
+    fn get_mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_program_ram(&self) -> &ProgramRam {
+        &self.program_ram
+    }
+
+    fn get_program_ram_mut(&mut self) -> &mut ProgramRam {
+        &mut self.program_ram
+    }
+}
+
+#[test]
+fn switch_character_bank() {
+    let character_ram = (0..0x8000).map(|i| (i / CHARACTER_BANK_SIZE) as u8).collect();
+    let mut mapper = Cnrom::new(Cassette {
+        mirroring: Mirroring::Horizontal,
+        character_ram,
+        program_rom: vec![0; 0x4000],
+        mapper: 3,
+        program_ram_size: 0x2000,
+        has_battery: false,
+    });
+    assert_eq!(mapper.read_character(0x0010), 0);
+    mapper.write(0x8000, 2);
+    assert_eq!(mapper.read_character(0x0010), 2);
+    mapper.write(0xFFFF, 5);
+    assert_eq!(mapper.read_character(0x1FFF), 1);
+}
@@ -0,0 +1,266 @@
+use super::{CharacterMemory, Mapper, Mirroring, ProgramRam};
+use nes::parser::Cassette;
+use nes::rom::Rom;
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::{Addr, Data};
+
+const PROGRAM_BANK_SIZE: usize = 0x4000;
+const CHARACTER_BANK_SIZE: usize = 0x1000;
+// SUROM selects the outer 256KiB program rom with bit4 of the character bank register.
+const PROGRAM_OUTER_BANK_COUNT: usize = 16;
+const SHIFT_REGISTER_INIT: Data = 0x10;
+
+// Mapper1 (SxROM)
+// Registers are loaded serially through bit0 of writes to 0x8000-0xFFFF.
+// The fifth write copies the shift register to the register selected by the address.
+//
+// | addr          | register                                         |
+// +---------------+--------------------------------------------------+
+// | 0x8000-0x9FFF | Control   bit4: character mode 0: 8KiB, 1: 4KiB  |
+// |               |           bit3-2: program mode                   |
+// |               |             0, 1: switch 32KiB at 0x8000         |
+// |               |             2: fix first bank at 0x8000          |
+// |               |             3: fix last bank at 0xC000           |
+// |               |           bit1-0: mirroring                      |
+// | 0xA000-0xBFFF | Character bank 0                                 |
+// | 0xC000-0xDFFF | Character bank 1                                 |
+// | 0xE000-0xFFFF | Program bank  bit4: program ram 0: enable        |
+#[derive(Debug)]
+pub struct Mmc1 {
+    program_rom: Rom,
+    character_memory: CharacterMemory,
+    program_ram: ProgramRam,
+    shift_register: Data,
+    control: Data,
+    character_bank0: Data,
+    character_bank1: Data,
+    program_bank: Data,
+    has_written: bool,
+}
+
+impl Mmc1 {
+    pub fn new(cassette: Cassette) -> Self {
+        Mmc1 {
+            program_rom: Rom::new(cassette.program_rom),
+            character_memory: CharacterMemory::new(cassette.character_ram),
+            program_ram: ProgramRam::new(cassette.program_ram_size, cassette.has_battery),
+            shift_register: SHIFT_REGISTER_INIT,
+            control: 0x0C,
+            character_bank0: 0,
+            character_bank1: 0,
+            program_bank: 0,
+            has_written: false,
+        }
+    }
+
+    fn write_register(&mut self, addr: Addr, data: Data) {
+        // Writes on consecutive cycles (the dummy write of read-modify-write instructions)
+        // are ignored except for the first.
+        if self.has_written {
+            return;
+        }
+        self.has_written = true;
+        if data & 0x80 == 0x80 {
+            self.shift_register = SHIFT_REGISTER_INIT;
+            self.control |= 0x0C;
+            return;
+        }
+        let is_filled = self.shift_register & 0x01 == 0x01;
+        self.shift_register = (self.shift_register >> 1) | ((data & 0x01) << 4);
+        if !is_filled {
+            return;
+        }
+        let value = self.shift_register;
+        match addr {
+            0x8000..=0x9FFF => self.control = value,
+            0xA000..=0xBFFF => self.character_bank0 = value,
+            0xC000..=0xDFFF => self.character_bank1 = value,
+            _ => self.program_bank = value,
+        }
+        self.shift_register = SHIFT_REGISTER_INIT;
+    }
+
+    fn is_program_ram_enable(&self) -> bool {
+        self.program_bank & 0x10 == 0x00
+    }
+
+    fn get_program_addr(&self, addr: Addr) -> usize {
+        let bank_count = self.program_rom.size() / PROGRAM_BANK_SIZE;
+        let outer = if bank_count > PROGRAM_OUTER_BANK_COUNT {
+            (self.character_bank0 as usize & 0x10) / 0x10 * PROGRAM_OUTER_BANK_COUNT
+        } else {
+            0
+        };
+        let last = bank_count.min(PROGRAM_OUTER_BANK_COUNT) - 1;
+        let selected = (self.program_bank & 0x0F) as usize;
+        let is_upper = addr >= 0xC000;
+        let bank = match (self.control >> 2) & 0x03 {
+            0 | 1 => (selected & 0x0E) | if is_upper { 1 } else { 0 },
+            2 => if is_upper { selected } else { 0 },
+            _ => if is_upper { last } else { selected },
+        };
+        ((outer + bank) % bank_count) * PROGRAM_BANK_SIZE + (addr as usize & 0x3FFF)
+    }
+
+    fn get_character_addr(&self, addr: Addr) -> usize {
+        let bank_count = self.character_memory.size() / CHARACTER_BANK_SIZE;
+        let is_upper = addr >= 0x1000;
+        let bank = if self.control & 0x10 == 0x00 {
+            (self.character_bank0 & 0x1E) as usize | if is_upper { 1 } else { 0 }
+        } else if is_upper {
+            self.character_bank1 as usize
+        } else {
+            self.character_bank0 as usize
+        };
+        (bank % bank_count) * CHARACTER_BANK_SIZE + (addr as usize & 0x0FFF)
+    }
+}
+
+impl Snapshot for Mmc1 {
+    fn save(&self, writer: &mut StateWriter) {
+        self.character_memory.save(writer);
+        self.program_ram.save(writer);
+        writer.write_u8(self.shift_register);
+        writer.write_u8(self.control);
+        writer.write_u8(self.character_bank0);
+        writer.write_u8(self.character_bank1);
+        writer.write_u8(self.program_bank);
+        writer.write_bool(self.has_written);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.character_memory.load(reader);
+        self.program_ram.load(reader);
+        self.shift_register = reader.read_u8();
+        self.control = reader.read_u8();
+        self.character_bank0 = reader.read_u8();
+        self.character_bank1 = reader.read_u8();
+        self.program_bank = reader.read_u8();
+        self.has_written = reader.read_bool();
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn read(&self, addr: Addr) -> Data {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_enable() => {
+                self.program_ram.read(addr)
+            }
+            0x8000..=0xFFFF => self.program_rom.read(self.get_program_addr(addr)),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Data) {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_enable() => {
+                self.program_ram.write(addr, data);
+            }
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => (),
+        }
+    }
+
+    fn read_character(&self, addr: Addr) -> Data {
+        self.character_memory.read(self.get_character_addr(addr))
+    }
+
+    fn write_character(&mut self, addr: Addr, data: Data) {
+        let addr = self.get_character_addr(addr);
+        self.character_memory.write(addr, data);
+    }
+
+    fn get_mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn get_program_ram(&self) -> &ProgramRam {
+        &self.program_ram
+    }
+
+    fn get_program_ram_mut(&mut self) -> &mut ProgramRam {
+        &mut self.program_ram
+    }
+
+    fn notify_cycle(&mut self, _cycle: usize) {
+        self.has_written = false;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_mmc1() -> Mmc1 {
+        let program_rom = (0..0x40000).map(|i| (i / PROGRAM_BANK_SIZE) as Data).collect();
+        Mmc1::new(Cassette {
+            mirroring: Mirroring::Horizontal,
+            character_ram: vec![],
+            program_rom,
+            mapper: 1,
+            program_ram_size: 0x2000,
+            has_battery: false,
+        })
+    }
+
+    fn write_serial(mapper: &mut Mmc1, addr: Addr, value: Data) {
+        for i in 0..5 {
+            mapper.write(addr, (value >> i) & 0x01);
+            mapper.notify_cycle(4);
+        }
+    }
+
+    #[test]
+    fn test_fix_last_bank_by_default() {
+        let mut mapper = create_mmc1();
+        write_serial(&mut mapper, 0xE000, 0x03);
+        assert_eq!(mapper.read(0x8000), 0x03);
+        assert_eq!(mapper.read(0xC000), 0x0F);
+    }
+
+    #[test]
+    fn test_switch_32k_program_bank() {
+        let mut mapper = create_mmc1();
+        write_serial(&mut mapper, 0x8000, 0x00);
+        write_serial(&mut mapper, 0xE000, 0x05);
+        assert_eq!(mapper.read(0x8000), 0x04);
+        assert_eq!(mapper.read(0xC000), 0x05);
+    }
+
+    #[test]
+    fn test_change_mirroring() {
+        let mut mapper = create_mmc1();
+        write_serial(&mut mapper, 0x8000, 0x02);
+        assert_eq!(mapper.get_mirroring(), Mirroring::Vertical);
+        write_serial(&mut mapper, 0x8000, 0x01);
+        assert_eq!(mapper.get_mirroring(), Mirroring::SingleScreenUpper);
+    }
+
+    #[test]
+    fn test_reset_shift_register() {
+        let mut mapper = create_mmc1();
+        write_serial(&mut mapper, 0x8000, 0x00);
+        mapper.write(0xE000, 0x01);
+        mapper.notify_cycle(4);
+        mapper.write(0x8000, 0x80);
+        mapper.notify_cycle(4);
+        write_serial(&mut mapper, 0xE000, 0x02);
+        assert_eq!(mapper.read(0x8000), 0x02);
+        assert_eq!(mapper.read(0xC000), 0x0F);
+    }
+
+    #[test]
+    fn test_ignore_consecutive_write() {
+        let mut mapper = create_mmc1();
+        mapper.write(0x8000, 0x80);
+        mapper.write(0x8000, 0x01);
+        mapper.notify_cycle(6);
+        write_serial(&mut mapper, 0xE000, 0x01);
+        assert_eq!(mapper.read(0x8000), 0x01);
+    }
+}
@@ -0,0 +1,310 @@
+use super::{CharacterMemory, Mapper, Mirroring, ProgramRam};
+use nes::parser::Cassette;
+use nes::rom::Rom;
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::{Addr, Data};
+
+const PROGRAM_BANK_SIZE: usize = 0x2000;
+const CHARACTER_BANK_SIZE: usize = 0x0400;
+
+// Mapper4 (TxROM)
+//
+// | addr                | register                                        |
+// +---------------------+-------------------------------------------------+
+// | 0x8000-0x9FFE even  | Bank select  bit7: character inversion          |
+// |                     |              bit6: program mode                 |
+// |                     |              bit2-0: bank register to update    |
+// | 0x8001-0x9FFF odd   | Bank data                                       |
+// | 0xA000-0xBFFE even  | Mirroring    0: vertical, 1: horizontal         |
+// | 0xA001-0xBFFF odd   | Program ram  bit7: enable, bit6: write protect  |
+// | 0xC000-0xDFFE even  | IRQ latch                                       |
+// | 0xC001-0xDFFF odd   | IRQ reload                                      |
+// | 0xE000-0xFFFE even  | IRQ disable and acknowledge                     |
+// | 0xE001-0xFFFF odd   | IRQ enable                                      |
+#[derive(Debug)]
+pub struct Mmc3 {
+    program_rom: Rom,
+    character_memory: CharacterMemory,
+    program_ram: ProgramRam,
+    is_four_screen: bool,
+    mirroring: Mirroring,
+    bank_select: Data,
+    banks: [usize; 8],
+    is_program_ram_enable: bool,
+    is_program_ram_protected: bool,
+    irq_latch: Data,
+    irq_counter: Data,
+    should_reload_irq: bool,
+    is_irq_enable: bool,
+    is_irq_asserted: bool,
+}
+
+impl Mmc3 {
+    pub fn new(cassette: Cassette) -> Self {
+        Mmc3 {
+            program_rom: Rom::new(cassette.program_rom),
+            character_memory: CharacterMemory::new(cassette.character_ram),
+            program_ram: ProgramRam::new(cassette.program_ram_size, cassette.has_battery),
+            is_four_screen: cassette.mirroring == Mirroring::FourScreen,
+            mirroring: cassette.mirroring,
+            bank_select: 0,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+            is_program_ram_enable: true,
+            is_program_ram_protected: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            should_reload_irq: false,
+            is_irq_enable: false,
+            is_irq_asserted: false,
+        }
+    }
+
+    fn write_register(&mut self, addr: Addr, data: Data) {
+        let is_even = addr & 0x01 == 0x00;
+        match addr {
+            0x8000..=0x9FFF if is_even => self.bank_select = data,
+            0x8000..=0x9FFF => {
+                let index = (self.bank_select & 0x07) as usize;
+                self.banks[index] = data as usize;
+            }
+            0xA000..=0xBFFF if is_even => {
+                if !self.is_four_screen {
+                    self.mirroring = if data & 0x01 == 0x01 {
+                        Mirroring::Horizontal
+                    } else {
+                        Mirroring::Vertical
+                    };
+                }
+            }
+            0xA000..=0xBFFF => {
+                self.is_program_ram_enable = data & 0x80 == 0x80;
+                self.is_program_ram_protected = data & 0x40 == 0x40;
+            }
+            0xC000..=0xDFFF if is_even => self.irq_latch = data,
+            0xC000..=0xDFFF => {
+                self.irq_counter = 0;
+                self.should_reload_irq = true;
+            }
+            _ if is_even => {
+                self.is_irq_enable = false;
+                self.is_irq_asserted = false;
+            }
+            _ => self.is_irq_enable = true,
+        }
+    }
+
+    fn get_program_addr(&self, addr: Addr) -> usize {
+        let bank_count = self.program_rom.size() / PROGRAM_BANK_SIZE;
+        let second_last = bank_count - 2;
+        let is_swapped = self.bank_select & 0x40 == 0x40;
+        let bank = match addr {
+            0x8000..=0x9FFF if is_swapped => second_last,
+            0x8000..=0x9FFF => self.banks[6],
+            0xA000..=0xBFFF => self.banks[7],
+            0xC000..=0xDFFF if is_swapped => self.banks[6],
+            0xC000..=0xDFFF => second_last,
+            _ => bank_count - 1,
+        };
+        (bank % bank_count) * PROGRAM_BANK_SIZE + (addr as usize & 0x1FFF)
+    }
+
+    fn get_character_addr(&self, addr: Addr) -> usize {
+        let bank_count = self.character_memory.size() / CHARACTER_BANK_SIZE;
+        // Inversion swaps the 2KiB banks at 0x0000 with the 1KiB banks at 0x1000.
+        let addr = if self.bank_select & 0x80 == 0x80 {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+        let bank = match addr {
+            0x0000..=0x07FF => (self.banks[0] & 0xFE) | ((addr as usize / 0x0400) & 0x01),
+            0x0800..=0x0FFF => (self.banks[1] & 0xFE) | ((addr as usize / 0x0400) & 0x01),
+            0x1000..=0x13FF => self.banks[2],
+            0x1400..=0x17FF => self.banks[3],
+            0x1800..=0x1BFF => self.banks[4],
+            _ => self.banks[5],
+        };
+        (bank % bank_count) * CHARACTER_BANK_SIZE + (addr as usize & 0x03FF)
+    }
+}
+
+impl Snapshot for Mmc3 {
+    fn save(&self, writer: &mut StateWriter) {
+        self.character_memory.save(writer);
+        self.program_ram.save(writer);
+        self.mirroring.save(writer);
+        writer.write_u8(self.bank_select);
+        for bank in self.banks.iter() {
+            writer.write_usize(*bank);
+        }
+        writer.write_bool(self.is_program_ram_enable);
+        writer.write_bool(self.is_program_ram_protected);
+        writer.write_u8(self.irq_latch);
+        writer.write_u8(self.irq_counter);
+        writer.write_bool(self.should_reload_irq);
+        writer.write_bool(self.is_irq_enable);
+        writer.write_bool(self.is_irq_asserted);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.character_memory.load(reader);
+        self.program_ram.load(reader);
+        self.mirroring.load(reader);
+        self.bank_select = reader.read_u8();
+        for bank in self.banks.iter_mut() {
+            *bank = reader.read_usize();
+        }
+        self.is_program_ram_enable = reader.read_bool();
+        self.is_program_ram_protected = reader.read_bool();
+        self.irq_latch = reader.read_u8();
+        self.irq_counter = reader.read_u8();
+        self.should_reload_irq = reader.read_bool();
+        self.is_irq_enable = reader.read_bool();
+        self.is_irq_asserted = reader.read_bool();
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn read(&self, addr: Addr) -> Data {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_enable => {
+                self.program_ram.read(addr)
+            }
+            0x8000..=0xFFFF => self.program_rom.read(self.get_program_addr(addr)),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Data) {
+        match addr {
+            0x6000..=0x7FFF if self.is_program_ram_enable && !self.is_program_ram_protected => {
+                self.program_ram.write(addr, data)
+            }
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => (),
+        }
+    }
+
+    fn read_character(&self, addr: Addr) -> Data {
+        self.character_memory.read(self.get_character_addr(addr))
+    }
+
+    fn write_character(&mut self, addr: Addr, data: Data) {
+        let addr = self.get_character_addr(addr);
+        self.character_memory.write(addr, data);
+    }
+
+    fn get_mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_program_ram(&self) -> &ProgramRam {
+        &self.program_ram
+    }
+
+    fn get_program_ram_mut(&mut self) -> &mut ProgramRam {
+        &mut self.program_ram
+    }
+
+    fn is_irq_asserted(&self) -> bool {
+        self.is_irq_asserted
+    }
+
+    // The counter is clocked by the rising edge of PPU A12,
+    // which happens once per line while rendering with the usual pattern table layout.
+    fn notify_scanline(&mut self) {
+        if self.irq_counter == 0 || self.should_reload_irq {
+            self.irq_counter = self.irq_latch;
+            self.should_reload_irq = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.is_irq_enable {
+            self.is_irq_asserted = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_mmc3() -> Mmc3 {
+        let program_rom = (0..0x20000).map(|i| (i / PROGRAM_BANK_SIZE) as Data).collect();
+        let character_ram = (0..0x20000).map(|i| (i / CHARACTER_BANK_SIZE) as Data).collect();
+        Mmc3::new(Cassette {
+            mirroring: Mirroring::Vertical,
+            character_ram,
+            program_rom,
+            mapper: 4,
+            program_ram_size: 0x2000,
+            has_battery: false,
+        })
+    }
+
+    #[test]
+    fn test_switch_program_bank() {
+        let mut mapper = create_mmc3();
+        mapper.write(0x8000, 0x06);
+        mapper.write(0x8001, 0x03);
+        mapper.write(0x8000, 0x07);
+        mapper.write(0x8001, 0x04);
+        assert_eq!(mapper.read(0x8000), 0x03);
+        assert_eq!(mapper.read(0xA000), 0x04);
+        assert_eq!(mapper.read(0xC000), 0x0E);
+        assert_eq!(mapper.read(0xE000), 0x0F);
+        mapper.write(0x8000, 0x46);
+        assert_eq!(mapper.read(0x8000), 0x0E);
+        assert_eq!(mapper.read(0xC000), 0x03);
+    }
+
+    #[test]
+    fn test_switch_character_bank_with_inversion() {
+        let mut mapper = create_mmc3();
+        mapper.write(0x8000, 0x00);
+        mapper.write(0x8001, 0x09);
+        mapper.write(0x8000, 0x02);
+        mapper.write(0x8001, 0x20);
+        assert_eq!(mapper.read_character(0x0000), 0x08);
+        assert_eq!(mapper.read_character(0x0400), 0x09);
+        assert_eq!(mapper.read_character(0x1000), 0x20);
+        mapper.write(0x8000, 0x80);
+        assert_eq!(mapper.read_character(0x0000), 0x20);
+        assert_eq!(mapper.read_character(0x1400), 0x09);
+    }
+
+    #[test]
+    fn test_change_mirroring() {
+        let mut mapper = create_mmc3();
+        mapper.write(0xA000, 0x01);
+        assert_eq!(mapper.get_mirroring(), Mirroring::Horizontal);
+        mapper.write(0xA000, 0x00);
+        assert_eq!(mapper.get_mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn test_protect_program_ram() {
+        let mut mapper = create_mmc3();
+        mapper.write(0x6000, 0xA5);
+        mapper.write(0xA001, 0xC0);
+        mapper.write(0x6000, 0x5A);
+        assert_eq!(mapper.read(0x6000), 0xA5);
+        mapper.write(0xA001, 0x00);
+        assert_eq!(mapper.read(0x6000), 0x00);
+    }
+
+    #[test]
+    fn test_assert_irq_after_latched_lines() {
+        let mut mapper = create_mmc3();
+        mapper.write(0xC000, 0x02);
+        mapper.write(0xC001, 0x00);
+        mapper.write(0xE001, 0x00);
+        mapper.notify_scanline();
+        mapper.notify_scanline();
+        assert!(!mapper.is_irq_asserted());
+        mapper.notify_scanline();
+        assert!(mapper.is_irq_asserted());
+        mapper.write(0xE000, 0x00);
+        assert!(!mapper.is_irq_asserted());
+    }
+}
@@ -0,0 +1,220 @@
+mod cnrom;
+mod mmc1;
+mod mmc3;
+mod nrom;
+mod nsf;
+mod uxrom;
+
+use self::cnrom::Cnrom;
+use self::mmc1::Mmc1;
+use self::mmc3::Mmc3;
+use self::nrom::Nrom;
+pub use self::nsf::{NsfBoard, DRIVER_ADDR};
+use self::uxrom::Uxrom;
+use nes::parser::{Cassette, RomError};
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::{Addr, Data};
+use std::fmt::Debug;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    SingleScreenLower,
+    SingleScreenUpper,
+    FourScreen,
+}
+
+// Cartridge board interface.
+// The CPU side covers 0x4020-0xFFFF and the PPU side covers the pattern tables 0x0000-0x1FFF.
+// Boards that do not use IRQs or scanline/cycle timing can leave the default implementations.
+// Bank registers and on-board ram are saved with the rest of the machine through Snapshot.
+pub trait Mapper: Debug + Snapshot {
+    fn read(&self, addr: Addr) -> Data;
+
+    fn write(&mut self, addr: Addr, data: Data);
+
+    fn read_character(&self, addr: Addr) -> Data;
+
+    fn write_character(&mut self, addr: Addr, data: Data);
+
+    fn get_mirroring(&self) -> Mirroring;
+
+    fn get_program_ram(&self) -> &ProgramRam;
+
+    fn get_program_ram_mut(&mut self) -> &mut ProgramRam;
+
+    fn is_irq_asserted(&self) -> bool {
+        false
+    }
+
+    // Called by the PPU once per rendered scanline.
+    fn notify_scanline(&mut self) {}
+
+    // Called with the number of CPU cycles consumed by the last instruction.
+    fn notify_cycle(&mut self, _cycle: usize) {}
+}
+
+pub fn create_mapper(cassette: Cassette) -> Result<Box<dyn Mapper>, RomError> {
+    let mapper: Box<dyn Mapper> = match cassette.mapper {
+        0 => Box::new(Nrom::new(cassette)),
+        1 => Box::new(Mmc1::new(cassette)),
+        2 => Box::new(Uxrom::new(cassette)),
+        3 => Box::new(Cnrom::new(cassette)),
+        4 => Box::new(Mmc3::new(cassette)),
+        n => return Err(RomError::UnsupportedMapper(n)),
+    };
+    Ok(mapper)
+}
+
+const CHARACTER_RAM_SIZE: usize = 0x2000;
+
+// Pattern table memory on the cartridge.
+// When the header reports no character rom pages, the board has 8KiB of writable character ram instead.
+#[derive(Debug)]
+pub struct CharacterMemory {
+    field: Vec<Data>,
+    is_ram: bool,
+}
+
+impl CharacterMemory {
+    pub fn new(buf: Vec<Data>) -> Self {
+        if buf.is_empty() {
+            CharacterMemory {
+                field: vec![0; CHARACTER_RAM_SIZE],
+                is_ram: true,
+            }
+        } else {
+            CharacterMemory {
+                field: buf,
+                is_ram: false,
+            }
+        }
+    }
+
+    pub fn read(&self, addr: usize) -> Data {
+        self.field[addr]
+    }
+
+    pub fn write(&mut self, addr: usize, data: Data) {
+        if self.is_ram {
+            self.field[addr] = data;
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.field.len()
+    }
+}
+
+// Only character ram is saved, rom is restored from the *.nes file.
+impl Snapshot for CharacterMemory {
+    fn save(&self, writer: &mut StateWriter) {
+        if self.is_ram {
+            writer.write_bytes(&self.field);
+        }
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        if self.is_ram {
+            reader.read_bytes(&mut self.field);
+        }
+    }
+}
+
+impl Snapshot for Mirroring {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(*self as u8);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        *self = match reader.read_u8() {
+            0 => Mirroring::Horizontal,
+            1 => Mirroring::Vertical,
+            2 => Mirroring::SingleScreenLower,
+            3 => Mirroring::SingleScreenUpper,
+            _ => Mirroring::FourScreen,
+        };
+    }
+}
+
+// Work ram on the cartridge at 0x6000-0x7FFF, mirrored when smaller than 8KiB.
+// Boards with the battery flag keep it while powered off, frontends persist it as *.sav.
+#[derive(Debug)]
+pub struct ProgramRam {
+    field: Vec<Data>,
+    has_battery: bool,
+}
+
+impl ProgramRam {
+    pub fn new(size: usize, has_battery: bool) -> Self {
+        ProgramRam {
+            field: vec![0; size],
+            has_battery,
+        }
+    }
+
+    pub fn read(&self, addr: Addr) -> Data {
+        self.field[(addr as usize - 0x6000) % self.field.len()]
+    }
+
+    pub fn write(&mut self, addr: Addr, data: Data) {
+        let len = self.field.len();
+        self.field[(addr as usize - 0x6000) % len] = data;
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    pub fn get_field(&self) -> &[Data] {
+        &self.field
+    }
+
+    // Copies a *.sav file, extra bytes on either side are ignored.
+    pub fn restore(&mut self, buf: &[Data]) {
+        for (dest, src) in self.field.iter_mut().zip(buf.iter()) {
+            *dest = *src;
+        }
+    }
+}
+
+impl Snapshot for ProgramRam {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.field);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        reader.read_bytes(&mut self.field);
+    }
+}
+
+// NROM like boards map 16KiB or 32KiB program rom to 0x8000-0xFFFF.
+// 16KiB rom is mirrored to 0xC000-0xFFFF.
+fn mirror_down_program_addr(addr: Addr, size: usize) -> usize {
+    (addr as usize - 0x8000) % size
+}
+
+#[test]
+fn test_program_ram_is_mirrored() {
+    let mut ram = ProgramRam::new(0x0800, true);
+    ram.write(0x6001, 0xA5);
+    assert_eq!(ram.read(0x6801), 0xA5);
+    ram.restore(&[1, 2]);
+    assert_eq!(&ram.get_field()[0..3], &[1, 2, 0]);
+}
+
+#[test]
+fn test_character_ram_is_writable_without_character_rom() {
+    let mut memory = CharacterMemory::new(vec![]);
+    memory.write(0x1FFF, 0xA5);
+    assert_eq!(memory.size(), 0x2000);
+    assert_eq!(memory.read(0x1FFF), 0xA5);
+}
+
+#[test]
+fn test_character_rom_is_not_writable() {
+    let mut memory = CharacterMemory::new(vec![0; 0x2000]);
+    memory.write(0x0000, 0xA5);
+    assert_eq!(memory.read(0x0000), 0x00);
+}
@@ -0,0 +1,75 @@
+use super::{mirror_down_program_addr, CharacterMemory, Mapper, Mirroring, ProgramRam};
+use nes::parser::Cassette;
+use nes::rom::Rom;
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::{Addr, Data};
+
+// Mapper0
+#[derive(Debug)]
+pub struct Nrom {
+    program_rom: Rom,
+    character_memory: CharacterMemory,
+    program_ram: ProgramRam,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(cassette: Cassette) -> Self {
+        Nrom {
+            program_rom: Rom::new(cassette.program_rom),
+            character_memory: CharacterMemory::new(cassette.character_ram),
+            program_ram: ProgramRam::new(cassette.program_ram_size, cassette.has_battery),
+            mirroring: cassette.mirroring,
+        }
+    }
+}
+
+impl Snapshot for Nrom {
+    fn save(&self, writer: &mut StateWriter) {
+        self.character_memory.save(writer);
+        self.program_ram.save(writer);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.character_memory.load(reader);
+        self.program_ram.load(reader);
+    }
+}
+
+impl Mapper for Nrom {
+    fn read(&self, addr: Addr) -> Data {
+        match addr {
+            0x6000..=0x7FFF => self.program_ram.read(addr),
+            0x8000..=0xFFFF => self
+                .program_rom
+                .read(mirror_down_program_addr(addr, self.program_rom.size())),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Data) {
+        if let 0x6000..=0x7FFF = addr {
+            self.program_ram.write(addr, data);
+        }
+    }
+
+    fn read_character(&self, addr: Addr) -> Data {
+        self.character_memory.read(addr as usize)
+    }
+
+    fn write_character(&mut self, addr: Addr, data: Data) {
+        self.character_memory.write(addr as usize, data);
+    }
+
+    fn get_mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_program_ram(&self) -> &ProgramRam {
+        &self.program_ram
+    }
+
+    fn get_program_ram_mut(&mut self) -> &mut ProgramRam {
+        &mut self.program_ram
+    }
+}
@@ -0,0 +1,238 @@
+use super::{Mapper, Mirroring, ProgramRam};
+use nes::parser::Nsf;
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::{Addr, Data};
+
+const BANK_SIZE: usize = 0x1000;
+const PROGRAM_RAM_SIZE: usize = 0x2000;
+const CPU_CLOCK: u64 = 1_789_772;
+
+// Entry point of the driver, the CPU starts here instead of the reset vector.
+pub const DRIVER_ADDR: Addr = 0x5F00;
+// Bit 7 is set when PLAY is due, any write acknowledges it.
+const PLAY_TIMER_ADDR: Addr = 0x5F20;
+
+// Synthetic cartridge for NSF music files.
+//
+// The program data is mapped to 0x8000-0xFFFF in 4KiB banks switched by 0x5FF8-0x5FFF,
+// or placed at the load address without bankswitching. There is 8KiB of ram at 0x6000-0x7FFF.
+// A small driver at 0x5F00 calls INIT with the track, then calls PLAY whenever the timer,
+// counted in CPU cycles at the rate of the header, expires.
+//
+// 5F00: SEI
+// 5F01: CLD
+// 5F02: LDX #$FF
+// 5F04: TXS
+// 5F05: LDA #track
+// 5F07: LDX #region  ; 0: NTSC, 1: PAL
+// 5F09: JSR init
+// 5F0C: BIT $5F20    ; wait for the timer
+// 5F0F: BPL $5F0C
+// 5F11: STA $5F20
+// 5F14: JSR play
+// 5F17: JMP $5F0C
+#[derive(Debug)]
+pub struct NsfBoard {
+    program: Vec<Data>,
+    banks: [usize; 8],
+    program_ram: ProgramRam,
+    driver: Vec<Data>,
+    play_period: u32,
+    play_timer: u32,
+    is_play_due: bool,
+}
+
+impl NsfBoard {
+    pub fn new(nsf: &Nsf, track: usize) -> Self {
+        // Without bankswitching, the data is placed at the load address as banks 0-7.
+        let (padding, banks) = if nsf.is_bankswitched() {
+            let mut banks = [0; 8];
+            for (bank, initial) in banks.iter_mut().zip(nsf.initial_banks.iter()) {
+                *bank = *initial as usize;
+            }
+            (nsf.load_addr as usize & (BANK_SIZE - 1), banks)
+        } else {
+            (nsf.load_addr as usize - 0x8000, [0, 1, 2, 3, 4, 5, 6, 7])
+        };
+        let mut program = vec![0; padding];
+        program.extend_from_slice(&nsf.data);
+        let size = program.len().div_ceil(BANK_SIZE).max(8) * BANK_SIZE;
+        program.resize(size, 0);
+        let play_period = (nsf.get_speed() as u64 * CPU_CLOCK / 1_000_000) as u32;
+        NsfBoard {
+            program,
+            banks,
+            program_ram: ProgramRam::new(PROGRAM_RAM_SIZE, false),
+            driver: build_driver(nsf, track),
+            play_period,
+            play_timer: 0,
+            is_play_due: false,
+        }
+    }
+
+    fn get_bank_count(&self) -> usize {
+        self.program.len() / BANK_SIZE
+    }
+}
+
+fn build_driver(nsf: &Nsf, track: usize) -> Vec<Data> {
+    let (init_low, init_high) = (nsf.init_addr as Data, (nsf.init_addr >> 8) as Data);
+    let (play_low, play_high) = (nsf.play_addr as Data, (nsf.play_addr >> 8) as Data);
+    let (timer_low, timer_high) = (PLAY_TIMER_ADDR as Data, (PLAY_TIMER_ADDR >> 8) as Data);
+    let (loop_low, loop_high) = ((DRIVER_ADDR + 0x0C) as Data, ((DRIVER_ADDR + 0x0C) >> 8) as Data);
+    vec![0x78,
+         0xD8,
+         0xA2, 0xFF,
+         0x9A,
+         0xA9, track as Data,
+         0xA2, nsf.is_pal as Data,
+         0x20, init_low, init_high,
+         0x2C, timer_low, timer_high,
+         0x10, 0xFB,
+         0x8D, timer_low, timer_high,
+         0x20, play_low, play_high,
+         0x4C, loop_low, loop_high]
+}
+
+impl Snapshot for NsfBoard {
+    fn save(&self, writer: &mut StateWriter) {
+        for bank in &self.banks {
+            writer.write_usize(*bank);
+        }
+        self.program_ram.save(writer);
+        writer.write_u32(self.play_timer);
+        writer.write_bool(self.is_play_due);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        for bank in self.banks.iter_mut() {
+            *bank = reader.read_usize();
+        }
+        self.program_ram.load(reader);
+        self.play_timer = reader.read_u32();
+        self.is_play_due = reader.read_bool();
+    }
+}
+
+impl Mapper for NsfBoard {
+    fn read(&self, addr: Addr) -> Data {
+        match addr {
+            PLAY_TIMER_ADDR if self.is_play_due => 0x80,
+            PLAY_TIMER_ADDR => 0x00,
+            0x5F00..=0x5FF7 => *self.driver.get((addr - DRIVER_ADDR) as usize).unwrap_or(&0),
+            0x6000..=0x7FFF => self.program_ram.read(addr),
+            0x8000..=0xFFFF => {
+                let bank = self.banks[(addr as usize - 0x8000) / BANK_SIZE] % self.get_bank_count();
+                self.program[bank * BANK_SIZE + (addr as usize & (BANK_SIZE - 1))]
+            }
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Data) {
+        match addr {
+            PLAY_TIMER_ADDR => self.is_play_due = false,
+            0x5FF8..=0x5FFF => self.banks[(addr - 0x5FF8) as usize] = data as usize,
+            0x6000..=0x7FFF => self.program_ram.write(addr, data),
+            _ => (),
+        }
+    }
+
+    fn read_character(&self, _addr: Addr) -> Data {
+        0
+    }
+
+    fn write_character(&mut self, _addr: Addr, _data: Data) {}
+
+    fn get_mirroring(&self) -> Mirroring {
+        Mirroring::Horizontal
+    }
+
+    fn get_program_ram(&self) -> &ProgramRam {
+        &self.program_ram
+    }
+
+    fn get_program_ram_mut(&mut self) -> &mut ProgramRam {
+        &mut self.program_ram
+    }
+
+    fn notify_cycle(&mut self, cycle: usize) {
+        self.play_timer += cycle as u32;
+        // A long stall can pass more than a period, PLAY is still called once.
+        if self.play_timer >= self.play_period {
+            self.play_timer %= self.play_period;
+            self.is_play_due = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_nsf(load_addr: Addr, initial_banks: [Data; 8]) -> Nsf {
+        Nsf {
+            track_count: 2,
+            starting_track: 0,
+            load_addr,
+            init_addr: 0x8000,
+            play_addr: 0x8003,
+            title: String::new(),
+            artist: String::new(),
+            copyright: String::new(),
+            ntsc_speed: 16_639,
+            pal_speed: 19_997,
+            initial_banks,
+            is_pal: false,
+            expansion_chips: 0,
+            // The first byte of each 4KiB is the number of the bank.
+            data: (0..0x3000).map(|i| (i / BANK_SIZE) as Data).collect(),
+        }
+    }
+
+    #[test]
+    fn test_load_without_bankswitching() {
+        let board = NsfBoard::new(&create_nsf(0x9000, [0; 8]), 1);
+        assert_eq!(board.read(0x8000), 0);
+        assert_eq!(board.read(0x9000), 0);
+        assert_eq!(board.read(0xA000), 1);
+        assert_eq!(board.read(0xB000), 2);
+        // LDA #track
+        assert_eq!(board.read(0x5F06), 1);
+    }
+
+    #[test]
+    fn test_switch_banks() {
+        let mut board = NsfBoard::new(&create_nsf(0x8000, [2, 1, 0, 0, 0, 0, 0, 0]), 0);
+        assert_eq!(board.read(0x8000), 2);
+        assert_eq!(board.read(0x9000), 1);
+        board.write(0x5FFF, 1);
+        assert_eq!(board.read(0xF000), 1);
+    }
+
+    #[test]
+    fn test_play_timer() {
+        let mut board = NsfBoard::new(&create_nsf(0x8000, [0; 8]), 0);
+        board.notify_cycle(29_000);
+        assert_eq!(board.read(PLAY_TIMER_ADDR), 0x00);
+        board.notify_cycle(1_000);
+        assert_eq!(board.read(PLAY_TIMER_ADDR), 0x80);
+        board.write(PLAY_TIMER_ADDR, 0);
+        assert_eq!(board.read(PLAY_TIMER_ADDR), 0x00);
+    }
+
+    #[test]
+    fn test_play_timer_without_speed() {
+        let mut nsf = create_nsf(0x8000, [0; 8]);
+        nsf.ntsc_speed = 0;
+        let mut board = NsfBoard::new(&nsf, 0);
+        board.notify_cycle(1);
+        assert_eq!(board.read(PLAY_TIMER_ADDR), 0x00);
+        // 16639us of the standard rate, twice over by a stall.
+        board.notify_cycle(29_780 * 2);
+        assert_eq!(board.read(PLAY_TIMER_ADDR), 0x80);
+        board.write(PLAY_TIMER_ADDR, 0);
+        board.notify_cycle(29_700);
+        assert_eq!(board.read(PLAY_TIMER_ADDR), 0x00);
+    }
+}
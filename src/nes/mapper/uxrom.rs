@@ -0,0 +1,126 @@
+use super::{CharacterMemory, Mapper, Mirroring, ProgramRam};
+use nes::parser::Cassette;
+use nes::rom::Rom;
+use nes::state::{Snapshot, StateReader, StateWriter};
+use nes::types::{Addr, Data};
+
+const PROGRAM_BANK_SIZE: usize = 0x4000;
+
+// Mapper2
+// Any write to 0x8000-0xFFFF selects the 16KiB program bank at 0x8000-0xBFFF.
+// 0xC000-0xFFFF is fixed to the last bank.
+#[derive(Debug)]
+pub struct Uxrom {
+    program_rom: Rom,
+    character_memory: CharacterMemory,
+    program_ram: ProgramRam,
+    mirroring: Mirroring,
+    bank: usize,
+}
+
+impl Uxrom {
+    pub fn new(cassette: Cassette) -> Self {
+        Uxrom {
+            program_rom: Rom::new(cassette.program_rom),
+            character_memory: CharacterMemory::new(cassette.character_ram),
+            program_ram: ProgramRam::new(cassette.program_ram_size, cassette.has_battery),
+            mirroring: cassette.mirroring,
+            bank: 0,
+        }
+    }
+
+    fn get_bank_count(&self) -> usize {
+        self.program_rom.size() / PROGRAM_BANK_SIZE
+    }
+}
+
+impl Snapshot for Uxrom {
+    fn save(&self, writer: &mut StateWriter) {
+        self.character_memory.save(writer);
+        self.program_ram.save(writer);
+        writer.write_usize(self.bank);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.character_memory.load(reader);
+        self.program_ram.load(reader);
+        self.bank = reader.read_usize();
+    }
+}
+
+impl Mapper for Uxrom {
+    fn read(&self, addr: Addr) -> Data {
+        match addr {
+            0x6000..=0x7FFF => self.program_ram.read(addr),
+            0x8000..=0xBFFF => self
+                .program_rom
+                .read(self.bank * PROGRAM_BANK_SIZE + (addr as usize - 0x8000)),
+            0xC000..=0xFFFF => self
+                .program_rom
+                .read((self.get_bank_count() - 1) * PROGRAM_BANK_SIZE + (addr as usize - 0xC000)),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Data) {
+        match addr {
+            0x6000..=0x7FFF => self.program_ram.write(addr, data),
+            0x8000..=0xFFFF => self.bank = data as usize % self.get_bank_count(),
+            _ => (),
+        }
+    }
+
+    fn read_character(&self, addr: Addr) -> Data {
+        self.character_memory.read(addr as usize)
+    }
+
+    fn write_character(&mut self, addr: Addr, data: Data) {
+        self.character_memory.write(addr as usize, data);
+    }
+
+    fn get_mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_program_ram(&self) -> &ProgramRam {
+        &self.program_ram
+    }
+
+    fn get_program_ram_mut(&mut self) -> &mut ProgramRam {
+        &mut self.program_ram
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_uxrom() -> Uxrom {
+        let program_rom = (0..0x20000).map(|i| (i / PROGRAM_BANK_SIZE) as Data).collect();
+        Uxrom::new(Cassette {
+            mirroring: Mirroring::Vertical,
+            character_ram: vec![],
+            program_rom,
+            mapper: 2,
+            program_ram_size: 0x2000,
+            has_battery: false,
+        })
+    }
+
+    #[test]
+    fn test_switch_program_bank() {
+        let mut mapper = create_uxrom();
+        assert_eq!(mapper.read(0x8000), 0x00);
+        assert_eq!(mapper.read(0xFFFF), 0x07);
+        mapper.write(0x8000, 0x05);
+        assert_eq!(mapper.read(0xBFFF), 0x05);
+        assert_eq!(mapper.read(0xC000), 0x07);
+    }
+
+    #[test]
+    fn test_write_character_ram() {
+        let mut mapper = create_uxrom();
+        mapper.write_character(0x1234, 0xA5);
+        assert_eq!(mapper.read_character(0x1234), 0xA5);
+    }
+}
@@ -4,107 +4,307 @@ mod cpu;
 mod cpu_registers;
 mod dma;
 mod helper;
+mod interrupts;
 mod keypad;
-mod mmc;
+mod mapper;
 mod parser;
 mod ppu;
 mod ram;
 mod renderer;
+mod rewind;
 mod rom;
+mod state;
 mod types;
 
+pub use self::apu::{Channel, MixerSettings, CHANNELS, DEFAULT_SAMPLE_RATE};
 pub use self::keypad::*;
-pub use self::ppu::background;
-pub use self::ppu::Tile;
-pub use self::ppu::{Sprite, SpritePosition, SpriteWithCtx};
+pub use self::parser::{Nsf, RomError, EXPANSION_FDS, EXPANSION_MMC5, EXPANSION_N163, EXPANSION_SUNSOFT_5B,
+                       EXPANSION_VRC6, EXPANSION_VRC7};
+pub use self::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
 pub use self::renderer::*;
+pub use self::rewind::Rewind;
+pub use self::state::{StateError, STATE_VERSION};
 
 use self::apu::*;
 use self::bus::cpu_bus;
+use self::cpu_registers::CpuRegisters;
 use self::dma::*;
-use self::mmc::*;
+use self::interrupts::*;
+use self::mapper::*;
 use self::ppu::*;
 use self::ram::Ram;
-use self::rom::Rom;
-use nes::types::Data;
+use self::state::{Snapshot, StateReader, StateWriter};
+use nes::types::{Addr, Data};
 
 const DMA_CYCLES: u16 = 514;
+const RESET_CYCLES: u16 = 7;
 
 #[derive(Debug)]
 pub struct Context {
     ppu: Ppu,
-    program_rom: Rom,
     work_ram: Ram,
     cpu_registers: cpu_registers::Registers,
     keypad: Keypad,
     dma: Dma,
     apu: Apu,
-    nmi: bool,
+    interrupts: Interrupts,
     renderer: Renderer,
-    mmc: Mmc,
+    mapper: Box<dyn Mapper>,
+    rom_hash: u64,
+    // Set when playing an *.nsf file instead of a cartridge.
+    nsf: Option<Nsf>,
+    track: usize,
+    // CPU cycles since reset for traces, not kept in save states.
+    cycles: usize,
 }
 
 pub fn reset(ctx: &mut Context) {
+    ctx.cycles = RESET_CYCLES as usize;
+    if ctx.nsf.is_some() {
+        let track = ctx.track;
+        ctx.select_track(track);
+        return;
+    }
     let mut cpu_bus = cpu_bus::Bus::new(
-        &ctx.program_rom,
         &mut ctx.work_ram,
         &mut ctx.ppu,
         &mut ctx.apu,
         &mut ctx.keypad,
         &mut ctx.dma,
-        &mut ctx.mmc,
+        &mut *ctx.mapper,
     );
     cpu::reset(&mut ctx.cpu_registers, &mut cpu_bus);
+    // The PPU keeps running during the reset sequence.
+    ctx.ppu.run((RESET_CYCLES * 3) as usize, &mut ctx.interrupts, &mut *ctx.mapper);
 }
 
+// Runs until the next frame is rendered.
+// The frame and the audio samples generated in the meantime are kept in the context until the next run.
 pub fn run(ctx: &mut Context, key_state: u8) {
     ctx.keypad.update(key_state);
-    loop {
-        let cycle: u16 = if ctx.dma.should_run() {
-            ctx.dma.run(&ctx.work_ram, &mut ctx.ppu);
-            DMA_CYCLES
-        } else {
-            let mut cpu_bus = cpu_bus::Bus::new(
-                &ctx.program_rom,
-                &mut ctx.work_ram,
-                &mut ctx.ppu,
-                &mut ctx.apu,
-                &mut ctx.keypad,
-                &mut ctx.dma,
-                &mut ctx.mmc,
-            );
-            cpu::run(&mut ctx.cpu_registers, &mut cpu_bus, &mut ctx.nmi) as u16
-        };
-        ctx.apu.run(cycle);
-        let is_ready = ctx.ppu.run((cycle * 3) as usize, &mut ctx.nmi, &ctx.mmc);
-        if is_ready {
-            if ctx.ppu.background.0.len() != 0 {
-                ctx.renderer.render(&ctx.ppu.background.0, &ctx.ppu.sprites);
+    ctx.apu.clear_samples();
+    while !step(ctx, None) {}
+}
+
+// Runs a single instruction, or a pending DMA, with the rest of the machine.
+// `trace` is called before the instruction with a line of it in the format of Nintendulator's log,
+// the one of nestest.log. Returns true when a frame is rendered.
+pub fn step_with_trace<F: FnMut(&str)>(ctx: &mut Context, mut trace: F) -> bool {
+    step(ctx, Some(&mut trace))
+}
+
+fn step(ctx: &mut Context, trace: Option<&mut dyn FnMut(&str)>) -> bool {
+    let cycle: u16 = if ctx.dma.should_run() {
+        ctx.dma.run(&ctx.work_ram, &mut ctx.ppu);
+        DMA_CYCLES
+    } else if let Some(stall) = ctx.apu.take_stall_cycles() {
+        stall
+    } else {
+        let position = (ctx.ppu.line, ctx.ppu.cycle);
+        let cycles = ctx.cycles;
+        let mut cpu_bus = cpu_bus::Bus::new(
+            &mut ctx.work_ram,
+            &mut ctx.ppu,
+            &mut ctx.apu,
+            &mut ctx.keypad,
+            &mut ctx.dma,
+            &mut *ctx.mapper,
+        );
+        match trace {
+            Some(trace) => {
+                let hook = |registers: &_, code: &_, bus: &mut cpu_bus::Bus| {
+                    let peek = |addr| bus.peek(addr);
+                    trace(&cpu::trace::format_line(registers, code, peek, position, cycles))
+                };
+                cpu::run_with_hook(&mut ctx.cpu_registers, &mut cpu_bus, &mut ctx.interrupts, hook) as u16
             }
-            break;
+            None => cpu::run(&mut ctx.cpu_registers, &mut cpu_bus, &mut ctx.interrupts) as u16,
         }
+    };
+    ctx.cycles += cycle as usize;
+    ctx.mapper.notify_cycle(cycle as usize);
+    ctx.apu.run(cycle, &mut ctx.interrupts, &*ctx.mapper);
+    let is_ready = ctx.ppu.run((cycle * 3) as usize, &mut ctx.interrupts, &mut *ctx.mapper);
+    if ctx.mapper.is_irq_asserted() {
+        ctx.interrupts.assert_irq(IrqSource::Mapper);
+    } else {
+        ctx.interrupts.deassert_irq(IrqSource::Mapper);
     }
+    if is_ready {
+        ctx.renderer.render(ctx.ppu.get_frame());
+    }
+    is_ready
 }
 
 impl Context {
-    pub fn new(buf: &mut [Data]) -> Self {
-        let cassette = parser::parse(buf);
-        Context {
+    // Loads a *.nes or *.nsf file, an error is returned for broken files and unsupported mappers.
+    pub fn new(buf: &mut [Data]) -> Result<Self, RomError> {
+        let rom_hash = state::hash(buf);
+        let (mapper, nsf): (Box<dyn Mapper>, _) = if parser::is_nsf(buf) {
+            let nsf = parser::parse_nsf(buf)?;
+            (Box::new(NsfBoard::new(&nsf, nsf.starting_track)), Some(nsf))
+        } else {
+            (create_mapper(parser::parse(buf)?)?, None)
+        };
+        let track = nsf.as_ref().map_or(0, |nsf| nsf.starting_track);
+        Ok(Context {
             cpu_registers: cpu_registers::Registers::new(),
-            program_rom: Rom::new(cassette.program_rom),
-            ppu: Ppu::new(
-                cassette.character_ram,
-                PpuConfig {
-                    is_horizontal_mirror: cassette.is_horizontal_mirror,
-                },
-            ),
+            ppu: Ppu::new(),
             work_ram: Ram::new(vec![0; 0x0800]),
             keypad: Keypad::new(),
             dma: Dma::new(),
             apu: Apu::new(),
-            nmi: false,
-            mmc: Mmc::new(cassette.mapper, 0),
+            interrupts: Interrupts::new(),
+            mapper,
             renderer: Renderer::new(),
+            rom_hash,
+            nsf,
+            track,
+            cycles: 0,
+        })
+    }
+
+    // Header of the *.nsf file, None for cartridges.
+    pub fn get_nsf(&self) -> Option<&Nsf> {
+        self.nsf.as_ref()
+    }
+
+    // Track of the *.nsf file being played, from 0.
+    pub fn get_track(&self) -> usize {
+        self.track
+    }
+
+    // Starts playing the track of the *.nsf file from INIT, the last track is played when out of range.
+    // The machine is reset as the NSF spec requires, except for the PPU which is not used.
+    pub fn select_track(&mut self, track: usize) {
+        let nsf = match self.nsf {
+            Some(ref nsf) => nsf,
+            None => return,
+        };
+        self.track = track.min(nsf.track_count - 1);
+        self.mapper = Box::new(NsfBoard::new(nsf, self.track));
+        self.work_ram = Ram::new(vec![0; 0x0800]);
+        self.cpu_registers = cpu_registers::Registers::new();
+        self.cpu_registers.set_PC(DRIVER_ADDR);
+        self.dma = Dma::new();
+        self.interrupts = Interrupts::new();
+        for addr in 0x00..=0x13 {
+            self.apu.write(addr, 0x00);
+        }
+        self.apu.write(0x15, 0x00);
+        self.apu.write(0x15, 0x0F);
+        self.apu.write(0x17, 0x40);
+    }
+
+    // Serializes the whole machine. Call between frames, i.e. not from inside `run`.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        writer.write_usize(self.track);
+        self.cpu_registers.save(&mut writer);
+        self.work_ram.save(&mut writer);
+        self.ppu.save(&mut writer);
+        self.apu.save(&mut writer);
+        self.keypad.save(&mut writer);
+        self.dma.save(&mut writer);
+        self.interrupts.save(&mut writer);
+        self.mapper.save(&mut writer);
+        writer.finish(self.rom_hash)
+    }
+
+    // Restores a state taken by `save_state` with the same rom.
+    // The context is left untouched when the state is rejected.
+    pub fn load_state(&mut self, buf: &[u8]) -> Result<(), StateError> {
+        let mut reader = StateReader::open(buf, self.rom_hash)?;
+        let track = reader.read_usize();
+        // The driver of the NSF board is built for the track.
+        if let Some(ref nsf) = self.nsf {
+            self.track = track.min(nsf.track_count - 1);
+            self.mapper = Box::new(NsfBoard::new(nsf, self.track));
+        }
+        self.cpu_registers.load(&mut reader);
+        self.work_ram.load(&mut reader);
+        self.ppu.load(&mut reader);
+        self.apu.load(&mut reader);
+        self.keypad.load(&mut reader);
+        self.dma.load(&mut reader);
+        self.interrupts.load(&mut reader);
+        self.mapper.load(&mut reader);
+        Ok(())
+    }
+
+    // Disabling the limit shows every sprite on a line instead of the first 8,
+    // the overflow flag still behaves as the hardware does.
+    pub fn set_sprite_limit(&mut self, is_enabled: bool) {
+        self.ppu.set_sprite_limit(is_enabled);
+    }
+
+    pub fn get_frame_buffer(&self) -> &[u8] {
+        self.renderer.get_buf()
+    }
+
+    // Mono samples of -1.0 to 1.0 generated by the last run.
+    pub fn get_audio_samples(&self) -> &[f32] {
+        self.apu.get_samples()
+    }
+
+    // DEFAULT_SAMPLE_RATE until set, pending samples are dropped.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.apu.set_sample_rate(sample_rate);
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.apu.get_sample_rate()
+    }
+
+    // Generates the samples of each channel alone alongside the mix, e.g. to record them separately.
+    pub fn set_stems_enabled(&mut self, is_enabled: bool) {
+        self.apu.set_stems_enabled(is_enabled);
+    }
+
+    // Samples of the channel generated by the last run, empty unless the stems are enabled.
+    pub fn get_stem_samples(&self, channel: Channel) -> &[f32] {
+        self.apu.get_stem_samples(channel)
+    }
+
+    pub fn get_mixer_settings(&self) -> &MixerSettings {
+        self.apu.get_mixer_settings()
+    }
+
+    // Mutes, solos or scales channels from now on without touching the emulated state.
+    pub fn set_mixer_settings(&mut self, settings: MixerSettings) {
+        self.apu.set_mixer_settings(settings);
+    }
+
+    pub fn get_work_ram(&self) -> &[Data] {
+        &self.work_ram.field
+    }
+
+    // Battery backed program ram to persist as *.sav, None when the cartridge has no battery.
+    pub fn get_battery_ram(&self) -> Option<&[Data]> {
+        let ram = self.mapper.get_program_ram();
+        if ram.has_battery() {
+            Some(ram.get_field())
+        } else {
+            None
+        }
+    }
+
+    // Restores a *.sav file, call before the first run.
+    pub fn load_battery_ram(&mut self, buf: &[Data]) {
+        self.mapper.get_program_ram_mut().restore(buf);
+    }
+
+    // Jumps without the reset sequence, e.g. to 0xC000 for the automation mode of nestest.
+    pub fn set_program_counter(&mut self, addr: Addr) {
+        self.cpu_registers.set_PC(addr);
+    }
+
+    // Reads the CPU address space without side effects, registers of PPU and APU read as 0.
+    pub fn peek(&self, addr: Addr) -> Data {
+        match addr {
+            0x0000..=0x1FFF => self.work_ram.read(addr & 0x07FF),
+            0x4020..=0xFFFF => self.mapper.read(addr),
+            _ => 0,
         }
     }
 }
@@ -1,36 +1,73 @@
-use std::str;
+mod nsf;
+
+pub use self::nsf::*;
+use nes::mapper::Mirroring;
+use nes::types::Addr;
+use std::fmt;
 
 const NES_HEADER_SIZE: usize = 0x0010;
 const PROGRAM_ROM_SIZE: usize = 0x4000;
 const CHARACTER_ROM_SIZE: usize = 0x2000;
+const PROGRAM_RAM_SIZE: usize = 0x2000;
+
+#[derive(Debug, PartialEq)]
+pub enum RomError {
+    InvalidHeader,
+    Truncated,
+    UnsupportedMapper(u8),
+    UnsupportedLoadAddr(Addr),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RomError::InvalidHeader => write!(f, "not a *.nes or *.nsf file"),
+            RomError::Truncated => write!(f, "rom is shorter than its header tells"),
+            RomError::UnsupportedMapper(n) => write!(f, "unsupported mapper {}", n),
+            RomError::UnsupportedLoadAddr(addr) => {
+                write!(f, "unsupported load address {:04X} of *.nsf file", addr)
+            }
+        }
+    }
+}
 
 pub struct Cassette {
-    pub is_horizontal_mirror: bool,
+    pub mirroring: Mirroring,
     pub character_ram: Vec<u8>,
     pub program_rom: Vec<u8>,
     pub mapper: u8,
+    pub program_ram_size: usize,
+    pub has_battery: bool,
 }
 
-pub fn parse(buf: &mut [u8]) -> Cassette {
-    let name = buf[0..3].to_vec();
-    let ines = str::from_utf8(&name).unwrap();
-    if ines != "NES" {
-        panic!("Invalid *.nes file.")
-    };
+pub fn parse(buf: &mut [u8]) -> Result<Cassette, RomError> {
+    if buf.len() < NES_HEADER_SIZE || &buf[0..3] != b"NES" {
+        return Err(RomError::InvalidHeader);
+    }
     let program_rom_pages = buf[4] as usize;
-    println!("program rom size is {}", program_rom_pages);
     let character_rom_pages = buf[5] as usize;
-    println!("character rom size is {}", character_rom_pages);
-    // TODO: mirror flag, mapper number, etc.....
-    let is_horizontal_mirror = (buf[6] & 0x01) != 0x01;
+    let mirroring = if buf[6] & 0x08 == 0x08 {
+        Mirroring::FourScreen
+    } else if buf[6] & 0x01 == 0x01 {
+        Mirroring::Vertical
+    } else {
+        Mirroring::Horizontal
+    };
     let mapper = ((buf[6] & 0xF0) >> 4) | buf[7] & 0xF0;
-    println!("mapper type is {}", mapper);
+    let has_battery = buf[6] & 0x02 == 0x02;
+    // Byte 8 is the program ram size in 8KiB units, 0 means 8KiB for compatibility.
+    let program_ram_pages = (buf[8] as usize).max(1);
     let character_rom_start = NES_HEADER_SIZE + program_rom_pages * PROGRAM_ROM_SIZE;
     let character_rom_end = character_rom_start + character_rom_pages * CHARACTER_ROM_SIZE;
-    Cassette {
-        is_horizontal_mirror,
+    if buf.len() < character_rom_end {
+        return Err(RomError::Truncated);
+    }
+    Ok(Cassette {
+        mirroring,
         program_rom: buf[NES_HEADER_SIZE..character_rom_start].to_vec(),
         character_ram: buf[character_rom_start..character_rom_end].to_vec(),
         mapper,
-    }
+        program_ram_size: program_ram_pages * PROGRAM_RAM_SIZE,
+        has_battery,
+    })
 }
@@ -0,0 +1,139 @@
+use super::RomError;
+use nes::types::{Addr, Data};
+
+const NSF_HEADER_SIZE: usize = 0x0080;
+const MAGIC: &[u8; 5] = b"NESM\x1A";
+// Rates used when the header leaves them 0, in microseconds.
+const DEFAULT_NTSC_SPEED: u16 = 16_639;
+const DEFAULT_PAL_SPEED: u16 = 19_997;
+
+// Expansion sound chips of the 0x7B flags.
+pub const EXPANSION_VRC6: Data = 0x01;
+pub const EXPANSION_VRC7: Data = 0x02;
+pub const EXPANSION_FDS: Data = 0x04;
+pub const EXPANSION_MMC5: Data = 0x08;
+pub const EXPANSION_N163: Data = 0x10;
+pub const EXPANSION_SUNSOFT_5B: Data = 0x20;
+
+// NSF music file
+//
+// | offset | size | description                                   |
+// |--------|------|-----------------------------------------------|
+// | 0x00   | 5    | "NESM" 0x1A                                   |
+// | 0x05   | 1    | version                                       |
+// | 0x06   | 1    | number of tracks                              |
+// | 0x07   | 1    | starting track, from 1                        |
+// | 0x08   | 2    | load address                                  |
+// | 0x0A   | 2    | init address                                  |
+// | 0x0C   | 2    | play address                                  |
+// | 0x0E   | 32   | title                                         |
+// | 0x2E   | 32   | artist                                        |
+// | 0x4E   | 32   | copyright                                     |
+// | 0x6E   | 2    | NTSC play rate in microseconds                |
+// | 0x70   | 8    | bankswitch init values, all 0 without banking |
+// | 0x78   | 2    | PAL play rate in microseconds                 |
+// | 0x7A   | 1    | bit0: PAL, bit1: both NTSC and PAL            |
+// | 0x7B   | 1    | expansion sound chips                         |
+// | 0x80   | -    | program data                                  |
+#[derive(Debug, Clone)]
+pub struct Nsf {
+    pub track_count: usize,
+    // From 0, the header counts from 1.
+    pub starting_track: usize,
+    pub load_addr: Addr,
+    pub init_addr: Addr,
+    pub play_addr: Addr,
+    pub title: String,
+    pub artist: String,
+    pub copyright: String,
+    // 0 in some files, `get_speed` falls back to the standard rates.
+    pub ntsc_speed: u16,
+    pub pal_speed: u16,
+    pub initial_banks: [Data; 8],
+    pub is_pal: bool,
+    pub expansion_chips: Data,
+    pub data: Vec<Data>,
+}
+
+impl Nsf {
+    pub fn is_bankswitched(&self) -> bool {
+        self.initial_banks.iter().any(|bank| *bank != 0)
+    }
+
+    // Microseconds between PLAY calls.
+    pub fn get_speed(&self) -> u16 {
+        match (self.is_pal, self.ntsc_speed, self.pal_speed) {
+            (true, _, 0) => DEFAULT_PAL_SPEED,
+            (true, _, speed) => speed,
+            (false, 0, _) => DEFAULT_NTSC_SPEED,
+            (false, speed, _) => speed,
+        }
+    }
+}
+
+pub fn is_nsf(buf: &[u8]) -> bool {
+    buf.len() >= NSF_HEADER_SIZE && &buf[0..5] == MAGIC
+}
+
+pub fn parse_nsf(buf: &[u8]) -> Result<Nsf, RomError> {
+    if !is_nsf(buf) {
+        return Err(RomError::InvalidHeader);
+    }
+    let read_word = |offset: usize| buf[offset] as u16 | (buf[offset + 1] as u16) << 8;
+    let read_text = |offset: usize| {
+        let field = &buf[offset..offset + 32];
+        let len = field.iter().position(|c| *c == 0).unwrap_or(32);
+        String::from_utf8_lossy(&field[..len]).into_owned()
+    };
+    let mut initial_banks = [0; 8];
+    initial_banks.copy_from_slice(&buf[0x70..0x78]);
+    let nsf = Nsf {
+        track_count: (buf[0x06] as usize).max(1),
+        starting_track: (buf[0x07] as usize).max(1) - 1,
+        load_addr: read_word(0x08),
+        init_addr: read_word(0x0A),
+        play_addr: read_word(0x0C),
+        title: read_text(0x0E),
+        artist: read_text(0x2E),
+        copyright: read_text(0x4E),
+        ntsc_speed: read_word(0x6E),
+        pal_speed: read_word(0x78),
+        initial_banks,
+        // Tunes for both regions are played as NTSC.
+        is_pal: buf[0x7A] & 0x03 == 0x01,
+        expansion_chips: buf[0x7B],
+        data: buf[NSF_HEADER_SIZE..].to_vec(),
+    };
+    if !nsf.is_bankswitched() && nsf.load_addr < 0x8000 {
+        return Err(RomError::UnsupportedLoadAddr(nsf.load_addr));
+    }
+    Ok(nsf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_nsf() {
+        let mut buf = vec![0; NSF_HEADER_SIZE + 4];
+        buf[0..5].copy_from_slice(MAGIC);
+        buf[0x06] = 12;
+        buf[0x07] = 3;
+        buf[0x08..0x0E].copy_from_slice(&[0x00, 0x80, 0x03, 0x80, 0x06, 0x80]);
+        buf[0x0E..0x13].copy_from_slice(b"Title");
+        buf[0x72] = 2;
+        buf[0x7B] = EXPANSION_VRC6 | EXPANSION_FDS;
+        let nsf = parse_nsf(&buf).unwrap();
+        assert_eq!(nsf.track_count, 12);
+        assert_eq!(nsf.starting_track, 2);
+        assert_eq!((nsf.load_addr, nsf.init_addr, nsf.play_addr), (0x8000, 0x8003, 0x8006));
+        assert_eq!(nsf.title, "Title");
+        assert_eq!(nsf.artist, "");
+        assert_eq!(nsf.get_speed(), DEFAULT_NTSC_SPEED);
+        assert!(nsf.is_bankswitched());
+        assert_eq!(nsf.expansion_chips & EXPANSION_FDS, EXPANSION_FDS);
+        assert_eq!(nsf.data.len(), 4);
+        assert!(!is_nsf(&buf[0..0x7F]));
+    }
+}
@@ -1,65 +1,111 @@
-// use std::cell::Cell;
-
-use super::super::mmc::*;
+use super::super::mapper::{Mapper, Mirroring};
 use super::super::ram::Ram;
 use super::super::types::{Addr, Data};
-use super::palette::*;
-use super::sprite_utils::*;
-use super::tile::Tile;
+use super::sprite_utils::mirror_down_name_table_addr;
 
+// Background pipeline of the PPU.
+// Each tile takes 8 dots, fetching the name table, attribute and two pattern bytes in turn.
+// The fetched tile is loaded into the lower half of the 16bit shift registers
+// while the upper half is being drawn.
+//
+// | dot (% 8) | fetch                    |
+// +-----------+--------------------------+
+// | 1         | name table byte          |
+// | 3         | attribute table byte     |
+// | 5         | pattern table low byte   |
+// | 7         | pattern table high byte  |
+//
+// The fetch address is v of the loopy registers, which steps to the next tile on dot % 8 == 0.
 #[derive(Debug)]
-pub struct BackgroundCtx {
-    pub tile: Tile,
-    pub scroll_x: Data,
-    pub scroll_y: Data,
-    pub is_enabled: bool,
+pub struct Background {
+    tile_id: Data,
+    attribute: Data,
+    pattern_low: Data,
+    pattern_high: Data,
+    pattern_shift_low: u16,
+    pattern_shift_high: u16,
+    attribute_shift_low: u16,
+    attribute_shift_high: u16,
 }
 
-pub type BackgroundField = Vec<BackgroundCtx>;
-
-#[derive(Debug)]
-pub struct Background(pub BackgroundField);
-
-const TILE_PER_LINE: u8 = 32;
-
 impl Background {
     pub fn new() -> Self {
-        Background(Vec::new())
-    }
-
-    pub fn clear(&mut self) {
-        self.0 = Vec::new();
+        Background {
+            tile_id: 0,
+            attribute: 0,
+            pattern_low: 0,
+            pattern_high: 0,
+            pattern_shift_low: 0,
+            pattern_shift_high: 0,
+            attribute_shift_low: 0,
+            attribute_shift_high: 0,
+        }
     }
 
-    pub fn build_line<P: PaletteRam>(
+    // Called on the fetching dots 1-256 and 321-336.
+    pub fn fetch(
         &mut self,
+        dot: usize,
+        v: Addr,
         vram: &Ram,
-        cram: &Ram,
-        palette: &P,
-        tile: (u8, u8),
-        scroll: (u8, u8),
-        config: &mut SpriteConfig,
-        mmc: &Mmc,
+        table_offset: Addr,
+        mapper: &dyn Mapper,
     ) {
-        // INFO: Horizontal offsets range from 0 to 255. "Normal" vertical offsets range from 0 to 239,
-        // while values of 240 to 255 are treated as -16 through -1 in a way, but tile data is incorrectly
-        // fetched from the attribute table.
-        let clamped_tile_y = tile.1 % 30;
-        let table_id_offset = if (tile.1 / 30) % 2 == 0 { 0 } else { 2 };
-        // background of a line.
-        // Build viewport + 1 tile for background scroll.
-        for x in 0..(TILE_PER_LINE + 1) {
-            let tile_x = x + tile.0;
-            let clamped_tile_x = tile_x % TILE_PER_LINE;
-            let name_table_id = ((tile_x / TILE_PER_LINE) % 2) + table_id_offset;
-            config.offset_addr_by_name_table = Some((name_table_id as Addr) * 0x400);
-            let position: SpritePosition = (clamped_tile_x as u8, clamped_tile_y as u8);
-            self.0.push(BackgroundCtx {
-                tile: Tile::new(vram, cram, palette, &position, &config, &mmc),
-                scroll_x: scroll.0,
-                scroll_y: scroll.1,
-                is_enabled: config.is_background_enable,
-            });
+        match (dot - 1) % 8 {
+            0 => {
+                self.reload();
+                self.tile_id = read_name_table(vram, v & 0x0FFF, mapper.get_mirroring());
+            }
+            2 => {
+                let addr = 0x03C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+                let attribute = read_name_table(vram, addr, mapper.get_mirroring());
+                // Each attribute byte covers 4x4 tiles, 2 bits for each 2x2 tiles.
+                let shift = ((v >> 4) & 0x04) | (v & 0x02);
+                self.attribute = (attribute >> shift) & 0x03;
+            }
+            4 => self.pattern_low = mapper.read_character(self.get_pattern_addr(v, table_offset)),
+            6 => {
+                self.pattern_high = mapper.read_character(self.get_pattern_addr(v, table_offset) + 8)
+            }
+            _ => (),
+        }
+    }
+
+    pub fn shift(&mut self) {
+        self.pattern_shift_low <<= 1;
+        self.pattern_shift_high <<= 1;
+        self.attribute_shift_low <<= 1;
+        self.attribute_shift_high <<= 1;
+    }
+
+    // Returns the palette entry 0-15, 0 is transparent.
+    pub fn get_pixel(&self, fine_x: Data) -> Data {
+        let mask = 0x8000 >> fine_x;
+        let bit = |v: u16| if v & mask != 0 { 1 } else { 0 };
+        let pixel = bit(self.pattern_shift_low) | bit(self.pattern_shift_high) << 1;
+        if pixel == 0 {
+            return 0;
         }
+        let palette = bit(self.attribute_shift_low) | bit(self.attribute_shift_high) << 1;
+        palette << 2 | pixel
+    }
+
+    fn reload(&mut self) {
+        self.pattern_shift_low = (self.pattern_shift_low & 0xFF00) | self.pattern_low as u16;
+        self.pattern_shift_high = (self.pattern_shift_high & 0xFF00) | self.pattern_high as u16;
+        let low = if self.attribute & 0x01 != 0 { 0xFF } else { 0x00 };
+        let high = if self.attribute & 0x02 != 0 { 0xFF } else { 0x00 };
+        self.attribute_shift_low = (self.attribute_shift_low & 0xFF00) | low;
+        self.attribute_shift_high = (self.attribute_shift_high & 0xFF00) | high;
+    }
+
+    // Fine Y of v selects the row in the tile.
+    fn get_pattern_addr(&self, v: Addr, table_offset: Addr) -> Addr {
+        table_offset + self.tile_id as Addr * 16 + ((v >> 12) & 0x07)
     }
 }
+
+fn read_name_table(vram: &Ram, addr: Addr, mirroring: Mirroring) -> Data {
+    vram.read(mirror_down_name_table_addr(addr, mirroring))
+}
+
@@ -1,49 +1,55 @@
-pub mod background;
+mod background;
 mod palette;
 mod registers;
 mod sprite;
 mod sprite_utils;
-pub mod tile;
 
-use self::super::mmc::Mmc;
+use self::super::interrupts::Interrupts;
+use self::super::mapper::Mapper;
 use self::super::ram::Ram;
-pub use self::background::*;
+use self::background::Background;
 pub use self::palette::*;
 use self::registers::*;
-pub use self::sprite::*;
-pub use self::sprite_utils::*;
-pub use self::tile::*;
+use self::sprite::Sprites;
+use super::state::{Snapshot, StateReader, StateWriter};
 use super::types::{Addr, Data};
 
-#[derive(Debug)]
-pub struct PpuConfig {
-    pub is_horizontal_mirror: bool,
-}
-
 #[derive(Debug)]
 pub struct PpuCtx<P: PaletteRam> {
     pub palette: P,
     pub vram: Box<Ram>,
-    pub cram: Box<Ram>,
     pub sprite_ram: Box<Ram>,
 }
 
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
 const CYCLES_PER_LINE: usize = 341;
+const VBLANK_LINE: usize = 241;
+const PRE_RENDER_LINE: usize = 261;
 
+// One frame is 262 lines of 341 dots.
+//
+// | line    | description                                          |
+// +---------+------------------------------------------------------+
+// | 0-239   | visible, a pixel per dot at 1-256                    |
+// | 240     | post-render, idle                                    |
+// | 241-260 | vertical blank, the flag and NMI are set at dot 1     |
+// | 261     | pre-render, fetches the first two tiles of line 0    |
 #[derive(Debug)]
 pub struct Ppu {
     pub cycle: usize,
     pub line: usize,
     pub registers: Registers,
     pub ctx: PpuCtx<Palette>,
-    pub sprites: SpritesWithCtx,
-    pub background: Background,
-    pub config: PpuConfig,
+    background: Background,
+    sprites: Sprites,
+    // 256x240 pixels of the palette color (0x00-0x3F) with the emphasis bits of $2001 in bit 8-6.
+    frame: Vec<u16>,
+    is_odd_frame: bool,
 }
 
 impl Ppu {
-    pub fn new(character_ram: Vec<u8>, config: PpuConfig) -> Ppu {
-        println!("cram length 0x{:x}", character_ram.len());
+    pub fn new() -> Ppu {
         Ppu {
             cycle: 0,
             line: 0,
@@ -51,112 +57,290 @@ impl Ppu {
             ctx: PpuCtx {
                 palette: Palette::new(),
                 vram: Box::new(Ram::new(vec![0; 0x2000])),
-                cram: Box::new(Ram::new(character_ram)),
                 sprite_ram: Box::new(Ram::new(vec![0; 0x0100])),
             },
-            sprites: Vec::new(),
             background: Background::new(),
-            config,
+            sprites: Sprites::new(),
+            frame: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            is_odd_frame: false,
         }
     }
 
-    pub fn read(&mut self, addr: Addr) -> Data {
-        self.registers.read(addr, &mut self.ctx)
+    pub fn read(&mut self, addr: Addr, mapper: &dyn Mapper) -> Data {
+        self.registers.read(addr, &mut self.ctx, mapper)
     }
 
-    pub fn write(&mut self, addr: Addr, data: Data) {
-        self.registers.write(addr, data, &mut self.ctx);
+    pub fn write(&mut self, addr: Addr, data: Data, mapper: &mut dyn Mapper) {
+        self.registers.write(addr, data, &mut self.ctx, mapper);
     }
 
-    // The PPU draws one line at 341 clocks and prepares for the next line.
-    // While drawing the BG and sprite at the first 256 clocks,
-    // it searches for sprites to be drawn on the next scan line.
-    // Get the pattern of the sprite searched with the remaining clock.
-    pub fn run(&mut self, cycle: usize, nmi: &mut bool, mmc: &Mmc) -> bool {
-        let cycle = self.cycle + cycle;
-        if cycle < CYCLES_PER_LINE {
-            self.cycle = cycle;
-            return false;
-        }
+    pub fn get_frame(&self) -> &[u16] {
+        &self.frame
+    }
 
-        if self.line == 0 {
-            self.background.clear();
-        }
+    // Hardware draws up to 8 sprites on a line, games flicker sprites to show more.
+    pub fn set_sprite_limit(&mut self, is_enabled: bool) {
+        self.sprites.set_unlimited(!is_enabled);
+    }
 
-        if self.has_sprite_hit(cycle) {
-            self.registers.set_sprite_hit();
+    // Runs the given number of dots.
+    // Returns true when the frame is completed, at the start of the vertical blank.
+    pub fn run(
+        &mut self,
+        cycle: usize,
+        interrupts: &mut Interrupts,
+        mapper: &mut dyn Mapper,
+    ) -> bool {
+        let mut is_ready = false;
+        for _ in 0..cycle {
+            is_ready |= self.step(interrupts, mapper);
         }
+        is_ready
+    }
 
-        self.cycle = cycle - CYCLES_PER_LINE;
-        self.line = self.line + 1;
-
-        let scroll_x = self.registers.get_scroll_x();
-        let scroll_y = self.registers.get_scroll_y();
-        if self.line <= 240 && self.line % 8 == 0 && scroll_y <= 240 {
-            let mut config = SpriteConfig {
-                offset_addr_by_name_table: None,
-                offset_addr_by_background_table: self.registers.get_background_table_offset(),
-                offset_addr_by_sprite_table: self.registers.get_sprite_table_offset(),
-                is_horizontal_mirror: self.config.is_horizontal_mirror,
-                is_background_enable: self.registers.is_background_enable(),
-            };
-            let tile_x = ((scroll_x as usize
-                + (self.registers.get_name_table_id() % 2) as usize * 256)
-                / 8) as u8;
-            let tile_y = self.get_scroll_tile_y();
-            self.background.build_line(
-                &self.ctx.vram,
-                &self.ctx.cram,
-                &self.ctx.palette,
-                (tile_x, tile_y),
-                (scroll_x, scroll_y),
-                &mut config,
-                &mmc,
-            );
-        }
+    pub fn transfer_sprite(&mut self, addr: Addr, data: Data) {
+        let addr = addr + self.registers.oam.get_addr();
+        self.ctx.sprite_ram.write(addr % 0x100, data);
+    }
 
-        if self.line == 241 {
+    fn step(&mut self, interrupts: &mut Interrupts, mapper: &mut dyn Mapper) -> bool {
+        let dot = self.cycle;
+        let line = self.line;
+        let is_rendering = self.is_rendering();
+        let mut is_ready = false;
+        if line < SCREEN_HEIGHT || line == PRE_RENDER_LINE {
+            if is_rendering {
+                self.step_fetch(dot, line, mapper);
+                // Boards like MMC3 count the lines fetched while background or sprites are enabled.
+                if dot == 260 {
+                    mapper.notify_scanline();
+                }
+            }
+            if line < SCREEN_HEIGHT && (1..=SCREEN_WIDTH).contains(&dot) {
+                self.draw_pixel(dot - 1, line);
+            }
+        }
+        if dot == 1 && line == VBLANK_LINE {
             self.registers.set_vblank();
-            self.registers.clear_sprite_hit();
             if self.registers.is_irq_enable() {
-                *nmi = true;
+                interrupts.assert_nmi();
             }
+            is_ready = true;
         }
-
-        if self.line >= 262 {
+        if dot == 1 && line == PRE_RENDER_LINE {
             self.registers.clear_vblank();
             self.registers.clear_sprite_hit();
-            *nmi = false;
-            self.line = 0;
-            self.sprites = build_sprites(
-                &self.ctx.cram,
-                &self.ctx.sprite_ram,
-                &self.ctx.palette,
-                self.registers.get_sprite_table_offset(),
-                self.registers.is_sprite_8x8(),
-                &mmc,
-            );
-            return true;
+            self.registers.clear_sprite_overflow();
+            interrupts.deassert_nmi();
+        }
+        self.cycle += 1;
+        // The last dot of the pre-render line is skipped on odd frames while rendering.
+        if line == PRE_RENDER_LINE && dot == CYCLES_PER_LINE - 2 && self.is_odd_frame && is_rendering {
+            self.cycle += 1;
+        }
+        if self.cycle >= CYCLES_PER_LINE {
+            self.cycle = 0;
+            self.line += 1;
+            if self.line > PRE_RENDER_LINE {
+                self.line = 0;
+                self.is_odd_frame = !self.is_odd_frame;
+            }
         }
-        false
+        is_ready
     }
 
-    pub fn transfer_sprite(&mut self, addr: Addr, data: Data) {
-        let addr = addr + self.registers.oam.get_addr();
-        self.ctx.sprite_ram.write(addr % 0x100, data);
+    fn step_fetch(&mut self, dot: usize, line: usize, mapper: &dyn Mapper) {
+        if matches!(dot, 2..=257 | 322..=337) {
+            self.background.shift();
+        }
+        if matches!(dot, 1..=256 | 321..=336) {
+            let v = self.registers.loopy.get_addr();
+            let table_offset = self.registers.get_background_table_offset();
+            self.background.fetch(dot, v, &self.ctx.vram, table_offset, mapper);
+            if (dot - 1) % 8 == 7 {
+                self.registers.loopy.increment_x();
+            }
+        }
+        match dot {
+            256 => self.registers.loopy.increment_y(),
+            257 => {
+                self.registers.loopy.copy_x();
+                self.registers.oam.reset_addr();
+                if line == PRE_RENDER_LINE {
+                    self.sprites.clear();
+                } else {
+                    let height = if self.registers.is_sprite_8x8() { 8 } else { 16 };
+                    let is_overflow = self.sprites.evaluate(
+                        line,
+                        &self.ctx.sprite_ram,
+                        height,
+                        self.registers.get_sprite_table_offset(),
+                        mapper,
+                    );
+                    if is_overflow {
+                        self.registers.set_sprite_overflow();
+                    }
+                }
+            }
+            280..=304 if line == PRE_RENDER_LINE => self.registers.loopy.copy_y(),
+            _ => (),
+        }
+    }
+
+    fn draw_pixel(&mut self, x: usize, line: usize) {
+        // "masked" is bit 1 and 2 of $2001, which show the left 8 pixels when set.
+        let background = if self.registers.is_background_enable()
+            && (x >= 8 || self.registers.is_background_masked())
+        {
+            self.background.get_pixel(self.registers.loopy.get_fine_x())
+        } else {
+            0
+        };
+        let sprite = if self.registers.is_sprite_enable()
+            && (x >= 8 || self.registers.is_sprite_masked())
+        {
+            self.sprites.get_pixel(x)
+        } else {
+            None
+        };
+        if let Some(ref sprite) = sprite {
+            if sprite.is_zero && background != 0 && self.can_hit_sprite_zero(x) {
+                self.registers.set_sprite_hit();
+            }
+        }
+        let entry = match sprite {
+            Some(ref sprite) if background == 0 || !sprite.is_behind_background => sprite.entry,
+            _ => background,
+        };
+        let mask = if self.registers.is_greyscale() { 0x30 } else { 0x3F };
+        let color = self.ctx.palette.read(entry as Addr) & mask;
+        let emphasis = self.registers.get_emphasis() as u16;
+        self.frame[line * SCREEN_WIDTH + x] = emphasis << 6 | color as u16;
+    }
+
+    fn is_rendering(&self) -> bool {
+        self.registers.is_background_enable() || self.registers.is_sprite_enable()
+    }
+
+    // Sprite 0 hit needs an opaque pixel of both layers, clipped pixels are transparent.
+    // It never happens at x=255.
+    fn can_hit_sprite_zero(&self, x: usize) -> bool {
+        self.registers.is_background_enable()
+            && self.registers.is_sprite_enable()
+            && x != SCREEN_WIDTH - 1
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Ppu::new()
+    }
+}
+
+// States are taken at the start of the vertical blank,
+// where nothing is left in the background and sprite pipelines.
+impl Snapshot for Ppu {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_usize(self.cycle);
+        writer.write_usize(self.line);
+        writer.write_bool(self.is_odd_frame);
+        self.registers.save(writer);
+        self.ctx.palette.save(writer);
+        self.ctx.vram.save(writer);
+        self.ctx.sprite_ram.save(writer);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.cycle = reader.read_usize();
+        self.line = reader.read_usize();
+        self.is_odd_frame = reader.read_bool();
+        self.registers.load(reader);
+        self.ctx.palette.load(reader);
+        self.ctx.vram.load(reader);
+        self.ctx.sprite_ram.load(reader);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nes::mapper::{create_mapper, Mirroring};
+    use nes::parser::Cassette;
+
+    fn create_nrom() -> Box<dyn Mapper> {
+        create_mapper(Cassette {
+            mirroring: Mirroring::Horizontal,
+            character_ram: vec![],
+            program_rom: vec![0; 0x4000],
+            mapper: 0,
+            program_ram_size: 0x2000,
+            has_battery: false,
+        })
+        .unwrap()
+    }
+
+    fn run_until(ppu: &mut Ppu, line: usize, interrupts: &mut Interrupts, mapper: &mut dyn Mapper) {
+        while ppu.line != line {
+            ppu.run(1, interrupts, mapper);
+        }
+    }
+
+    #[test]
+    fn test_frame_is_ready_at_vblank() {
+        let mut ppu = Ppu::new();
+        let mut interrupts = Interrupts::new();
+        let mut mapper = create_nrom();
+        ppu.write(0x0000, 0x80, &mut *mapper);
+        while !ppu.run(1, &mut interrupts, &mut *mapper) {}
+        assert_eq!((ppu.line, ppu.cycle), (VBLANK_LINE, 2));
+        assert!(interrupts.is_nmi_asserted());
     }
 
-    fn get_scroll_tile_y(&self) -> Data {
-        ((self.registers.get_scroll_y() as usize
-            + self.line
-            + ((self.registers.get_name_table_id() / 2) as usize * 240))
-            / 8) as Data
+    #[test]
+    fn test_backdrop_change_in_the_middle_of_frame() {
+        let mut ppu = Ppu::new();
+        let mut interrupts = Interrupts::new();
+        let mut mapper = create_nrom();
+        let write_backdrop = |ppu: &mut Ppu, color: Data, mapper: &mut dyn Mapper| {
+            ppu.write(0x0006, 0x3F, mapper);
+            ppu.write(0x0006, 0x00, mapper);
+            ppu.write(0x0007, color, mapper);
+        };
+        write_backdrop(&mut ppu, 0x01, &mut *mapper);
+        run_until(&mut ppu, 100, &mut interrupts, &mut *mapper);
+        write_backdrop(&mut ppu, 0x02, &mut *mapper);
+        run_until(&mut ppu, VBLANK_LINE, &mut interrupts, &mut *mapper);
+        assert_eq!(ppu.get_frame()[50 * SCREEN_WIDTH], 0x01);
+        assert_eq!(ppu.get_frame()[150 * SCREEN_WIDTH], 0x02);
     }
 
-    fn has_sprite_hit(&self, cycle: usize) -> bool {
-        let y = self.ctx.sprite_ram.read(0) as usize;
-        let x = self.ctx.sprite_ram.read(3) as usize;
-        // (y == self.line) && self.registers.is_sprite_enable()
-        (y == self.line) && x <= cycle && self.registers.is_sprite_enable()
+    #[test]
+    fn test_clip_left_background_with_greyscale_and_emphasis() {
+        let mut ppu = Ppu::new();
+        let mut interrupts = Interrupts::new();
+        let mut mapper = create_nrom();
+        let write = |ppu: &mut Ppu, addr: Addr, data: Data, mapper: &mut dyn Mapper| {
+            ppu.write(0x0006, (addr >> 8) as Data, mapper);
+            ppu.write(0x0006, addr as Data, mapper);
+            ppu.write(0x0007, data, mapper);
+        };
+        // The top row of tile 0 is opaque, every tile of the name table is 0.
+        write(&mut ppu, 0x0000, 0xFF, &mut *mapper);
+        write(&mut ppu, 0x3F00, 0x0F, &mut *mapper);
+        write(&mut ppu, 0x3F01, 0x16, &mut *mapper);
+        ppu.write(0x0005, 0x00, &mut *mapper);
+        ppu.write(0x0005, 0x00, &mut *mapper);
+        // Background without the left 8 pixels, greyscale and red emphasis.
+        ppu.write(0x0001, 0x29, &mut *mapper);
+        run_until(&mut ppu, PRE_RENDER_LINE, &mut interrupts, &mut *mapper);
+        run_until(&mut ppu, VBLANK_LINE, &mut interrupts, &mut *mapper);
+        // 0x0F and 0x16 turn into 0x00 and 0x10 in greyscale.
+        assert_eq!(ppu.get_frame()[7], 0x040);
+        assert_eq!(ppu.get_frame()[8], 0x050);
+        // Showing the left 8 pixels.
+        ppu.write(0x0001, 0x0A, &mut *mapper);
+        run_until(&mut ppu, PRE_RENDER_LINE, &mut interrupts, &mut *mapper);
+        run_until(&mut ppu, VBLANK_LINE, &mut interrupts, &mut *mapper);
+        assert_eq!(ppu.get_frame()[7], 0x16);
     }
 }
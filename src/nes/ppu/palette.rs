@@ -1,10 +1,5 @@
 use super::super::types::{Addr, Data};
-
-#[derive(Debug)]
-pub enum PaletteType {
-    Sprite,
-    Background,
-}
+use super::super::state::{Snapshot, StateReader, StateWriter};
 
 pub type PaletteList = Vec<u8>;
 
@@ -12,8 +7,6 @@ pub type PaletteList = Vec<u8>;
 pub struct Palette(PaletteList);
 
 pub trait PaletteRam {
-    fn get(&self, palette_id: u8, palette_type: PaletteType) -> PaletteList;
-
     fn read(&self, addr: Addr) -> Data;
 
     fn write(&mut self, addr: Addr, data: Data);
@@ -44,16 +37,6 @@ impl Palette {
 }
 
 impl PaletteRam for Palette {
-    fn get(&self, palette_id: u8, palette_type: PaletteType) -> PaletteList {
-        let offset = match palette_type {
-            PaletteType::Sprite => 0x10,
-            _ => 0x00,
-        };
-        let start = (palette_id * 4 + offset) as usize;
-        let end = start + 4;
-        (start..end).map(|p| self.read(p as Addr)).collect()
-    }
-
     fn read(&self, addr: Addr) -> Data {
         if self.is_sprite_mirror(addr) {
             return self.0[(addr - 0x10) as usize];
@@ -74,30 +57,36 @@ impl PaletteRam for Palette {
     }
 }
 
+impl Snapshot for Palette {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.0);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        reader.read_bytes(&mut self.0);
+    }
+}
+
 #[test]
-fn test_get_baclground_palette() {
+fn test_read_background_palette() {
     let mut p = Palette::new();
     for x in 0..4 {
         p.write(x, x as Data);
     }
-    let palette = p.get(0x00, PaletteType::Background);
-    assert_eq!(palette.len(), 0x4);
-    assert_eq!(palette[0], 0x0);
-    assert_eq!(palette[1], 0x1);
-    assert_eq!(palette[2], 0x2);
-    assert_eq!(palette[3], 0x3);
+    assert_eq!(p.read(0x00), 0x0);
+    assert_eq!(p.read(0x01), 0x1);
+    assert_eq!(p.read(0x02), 0x2);
+    assert_eq!(p.read(0x03), 0x3);
 }
 
 #[test]
-fn test_get_sprite_palette() {
+fn test_read_sprite_palette() {
     let mut p = Palette::new();
     for x in 0x10..0x14 {
         p.write(x, x as Data);
     }
-    let palette = p.get(0x00, PaletteType::Sprite);
-    assert_eq!(palette.len(), 0x4);
-    assert_eq!(palette[0], 0x10);
-    assert_eq!(palette[1], 0x11);
-    assert_eq!(palette[2], 0x12);
-    assert_eq!(palette[3], 0x13);
-}
\ No newline at end of file
+    assert_eq!(p.read(0x10), 0x10);
+    assert_eq!(p.read(0x11), 0x11);
+    assert_eq!(p.read(0x12), 0x12);
+    assert_eq!(p.read(0x13), 0x13);
+}
@@ -0,0 +1,197 @@
+use super::super::super::types::{Addr, Data};
+use super::super::super::state::{Snapshot, StateReader, StateWriter};
+
+// Internal scroll registers shared by $2000, $2005, $2006 and $2007 (named after loopy's document).
+//
+// | register | description                                         |
+// +----------+-----------------------------------------------------+
+// | v        | current vram address, also the render position      |
+// | t        | temporary vram address, the top left of the screen  |
+// | x        | fine X scroll                                       |
+// | w        | first or second write toggle of $2005 and $2006     |
+//
+// v and t have the same layout.
+//
+// yyy NN YYYYY XXXXX
+// ||| || ||||| +++++-- coarse X scroll
+// ||| || +++++-------- coarse Y scroll
+// ||| ++-------------- name table select
+// +++----------------- fine Y scroll
+#[derive(Debug)]
+pub struct Loopy {
+    v: Addr,
+    t: Addr,
+    fine_x: Data,
+    is_second_write: bool,
+}
+
+impl Loopy {
+    pub fn new() -> Self {
+        Loopy {
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            is_second_write: false,
+        }
+    }
+
+    pub fn get_addr(&self) -> Addr {
+        self.v
+    }
+
+    pub fn get_fine_x(&self) -> Data {
+        self.fine_x
+    }
+
+    // Reading $2002 resets the toggle.
+    pub fn reset_latch(&mut self) {
+        self.is_second_write = false;
+    }
+
+    // $2000 bit 1-0
+    pub fn write_name_table(&mut self, data: Data) {
+        self.t = (self.t & !0x0C00) | ((data as Addr & 0x03) << 10);
+    }
+
+    // $2005, X on the first write and Y on the second write.
+    pub fn write_scroll(&mut self, data: Data) {
+        if self.is_second_write {
+            self.t = (self.t & !0x73E0) | ((data as Addr & 0x07) << 12) | ((data as Addr >> 3) << 5);
+        } else {
+            self.t = (self.t & !0x001F) | (data as Addr >> 3);
+            self.fine_x = data & 0x07;
+        }
+        self.is_second_write = !self.is_second_write;
+    }
+
+    // $2006, upper byte on the first write and lower byte on the second write.
+    // The second write copies t to v, so games can change the scroll in the middle of the frame.
+    pub fn write_addr(&mut self, data: Data) {
+        if self.is_second_write {
+            self.t = (self.t & 0xFF00) | data as Addr;
+            self.v = self.t;
+        } else {
+            self.t = (self.t & 0x00FF) | ((data as Addr & 0x3F) << 8);
+        }
+        self.is_second_write = !self.is_second_write;
+    }
+
+    // After each $2007 access.
+    pub fn increment(&mut self, offset: Addr) {
+        self.v = self.v.wrapping_add(offset) & 0x7FFF;
+    }
+
+    // Dot 8, 16, ..., 256, 328 and 336 move to the next tile.
+    pub fn increment_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v &= !0x001F;
+            self.v ^= 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    // Dot 256 moves to the next pixel row.
+    pub fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !0x7000;
+        let mut y = (self.v & 0x03E0) >> 5;
+        if y == 29 {
+            y = 0;
+            self.v ^= 0x0800;
+        } else if y == 31 {
+            y = 0;
+        } else {
+            y += 1;
+        }
+        self.v = (self.v & !0x03E0) | (y << 5);
+    }
+
+    // Dot 257 restarts the line from the left of the screen.
+    pub fn copy_x(&mut self) {
+        self.v = (self.v & !0x041F) | (self.t & 0x041F);
+    }
+
+    // Dots 280-304 of the pre-render line restart the frame from the top of the screen.
+    pub fn copy_y(&mut self) {
+        self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+    }
+}
+
+impl Default for Loopy {
+    fn default() -> Self {
+        Loopy::new()
+    }
+}
+
+impl Snapshot for Loopy {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.v);
+        writer.write_u16(self.t);
+        writer.write_u8(self.fine_x);
+        writer.write_bool(self.is_second_write);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.v = reader.read_u16();
+        self.t = reader.read_u16();
+        self.fine_x = reader.read_u8();
+        self.is_second_write = reader.read_bool();
+    }
+}
+
+#[test]
+fn set_addr() {
+    let mut reg = Loopy::new();
+    reg.write_addr(0x2a);
+    reg.write_addr(0x55);
+    assert_eq!(reg.get_addr(), 0x2a55);
+}
+
+#[test]
+fn update_addr() {
+    let mut reg = Loopy::new();
+    reg.write_addr(0x2a);
+    reg.write_addr(0x55);
+    reg.increment(32);
+    assert_eq!(reg.get_addr(), 0x2a75);
+}
+
+#[test]
+fn share_toggle_between_scroll_and_addr() {
+    let mut reg = Loopy::new();
+    reg.write_name_table(0x01);
+    reg.write_scroll(0x7D);
+    assert_eq!(reg.get_fine_x(), 0x05);
+    // $2006 after $2005 is taken as the second write and overwrites the lower byte.
+    reg.write_addr(0x3F);
+    assert_eq!(reg.get_addr(), 0x043F);
+    reg.reset_latch();
+    reg.write_scroll(0x5E);
+    reg.write_scroll(0x5E);
+    reg.write_addr(0x00);
+    reg.write_addr(0x00);
+    assert_eq!(reg.get_addr(), 0x0000);
+}
+
+#[test]
+fn increment_y_wraps_to_next_name_table() {
+    let mut reg = Loopy::new();
+    reg.v = 0x7000 | (29 << 5);
+    reg.increment_y();
+    assert_eq!(reg.get_addr(), 0x0800);
+    reg.v = 0x7000 | (31 << 5);
+    reg.increment_y();
+    assert_eq!(reg.get_addr(), 0x0000);
+}
+
+#[test]
+fn increment_x_wraps_to_next_name_table() {
+    let mut reg = Loopy::new();
+    reg.v = 31;
+    reg.increment_x();
+    assert_eq!(reg.get_addr(), 0x0400);
+}
@@ -1,17 +1,17 @@
+mod loopy;
 mod oam;
-mod ppu_addr;
 mod ppu_data;
-mod ppu_scroll;
 
+use super::super::mapper::Mapper;
 use super::super::types::{Addr, Data};
 use super::super::Ram;
 use super::palette::*;
 use super::PpuCtx;
 // use super::super::helper::*;
+use self::loopy::Loopy;
 use self::oam::Oam;
-use self::ppu_addr::PpuAddr;
 use self::ppu_data::PpuData;
-use self::ppu_scroll::PpuScroll;
+use super::super::state::{Snapshot, StateReader, StateWriter};
 
 #[derive(Debug)]
 pub struct Registers {
@@ -19,9 +19,8 @@ pub struct Registers {
     pub ppu_ctrl2: Data,
     pub ppu_status: Data,
     pub oam: Oam,
-    pub ppu_addr: PpuAddr,
+    pub loopy: Loopy,
     pub ppu_data: PpuData,
-    pub ppu_scroll: PpuScroll,
 }
 
 // PPU power up state
@@ -48,9 +47,15 @@ pub struct Registers {
   */
 
 pub trait PpuRegisters {
-    fn read<P: PaletteRam>(&mut self, addr: Addr, ctx: &mut PpuCtx<P>) -> Data;
+    fn read<P: PaletteRam>(&mut self, addr: Addr, ctx: &mut PpuCtx<P>, mapper: &dyn Mapper) -> Data;
 
-    fn write<P: PaletteRam>(&mut self, addr: Addr, data: Data, ctx: &mut PpuCtx<P>);
+    fn write<P: PaletteRam>(
+        &mut self,
+        addr: Addr,
+        data: Data,
+        ctx: &mut PpuCtx<P>,
+        mapper: &mut dyn Mapper,
+    );
 
     fn is_sprite_8x8(&self) -> bool;
 
@@ -62,18 +67,16 @@ pub trait PpuRegisters {
 
     fn clear_sprite_hit(&mut self);
 
+    fn set_sprite_overflow(&mut self);
+
+    fn clear_sprite_overflow(&mut self);
+
     fn get_sprite_table_offset(&self) -> Addr;
 
     fn get_background_table_offset(&self) -> Addr;
 
     fn get_ppu_addr_increment_value(&self) -> usize;
 
-    fn get_name_table_id(&self) -> Data;
-
-    fn get_scroll_x(&self) -> Data;
-
-    fn get_scroll_y(&self) -> Data;
-
     fn is_irq_enable(&self) -> bool;
 
     fn is_background_enable(&self) -> bool;
@@ -83,6 +86,10 @@ pub trait PpuRegisters {
     fn is_background_masked(&self) -> bool;
 
     fn is_sprite_masked(&self) -> bool;
+
+    fn is_greyscale(&self) -> bool;
+
+    fn get_emphasis(&self) -> Data;
 }
 
 impl Registers {
@@ -92,9 +99,8 @@ impl Registers {
             ppu_ctrl2: 0,
             ppu_status: 0,
             oam: Oam::new(),
-            ppu_addr: PpuAddr::new(),
+            loopy: Loopy::new(),
             ppu_data: PpuData::new(),
-            ppu_scroll: PpuScroll::new(),
         }
     }
 
@@ -110,10 +116,8 @@ impl Registers {
     */
     fn read_status(&mut self) -> Data {
         let data = self.ppu_status;
-        self.ppu_scroll.enable_x();
         self.clear_vblank();
-        self.clear_sprite_hit();
-        self.ppu_addr.reser_latch();
+        self.loopy.reset_latch();
         data
     }
 
@@ -125,15 +129,16 @@ impl Registers {
         self.oam.write_data(sprite_ram, data);
     }
 
-    fn write_ppu_addr(&mut self, data: Data) {
-        self.ppu_addr.write(data);
+    fn write_ppu_ctrl1(&mut self, data: Data) {
+        self.ppu_ctrl1 = data;
+        self.loopy.write_name_table(data);
     }
 
-    fn read_ppu_data<P: PaletteRam>(&mut self, vram: &Ram, cram: &Ram, palette: &P) -> Data {
-        let addr = self.ppu_addr.get();
-        let data = self.ppu_data.read(vram, cram, addr, palette);
+    fn read_ppu_data<P: PaletteRam>(&mut self, vram: &Ram, palette: &P, mapper: &dyn Mapper) -> Data {
+        let addr = self.loopy.get_addr();
+        let data = self.ppu_data.read(vram, addr, palette, mapper);
         let v = self.get_ppu_addr_increment_value() as u16;
-        self.ppu_addr.update(v);
+        self.loopy.increment(v);
         data
     }
 
@@ -141,13 +146,33 @@ impl Registers {
         &mut self,
         data: Data,
         vram: &mut Ram,
-        cram: &mut Ram,
         palette: &mut P,
+        mapper: &mut dyn Mapper,
     ) {
-        let addr = self.ppu_addr.get();
-        self.ppu_data.write(vram, cram, addr, data, palette);
+        let addr = self.loopy.get_addr();
+        self.ppu_data.write(vram, addr, data, palette, mapper);
         let v = self.get_ppu_addr_increment_value() as u16;
-        self.ppu_addr.update(v);
+        self.loopy.increment(v);
+    }
+}
+
+impl Snapshot for Registers {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.ppu_ctrl1);
+        writer.write_u8(self.ppu_ctrl2);
+        writer.write_u8(self.ppu_status);
+        self.oam.save(writer);
+        self.loopy.save(writer);
+        self.ppu_data.save(writer);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.ppu_ctrl1 = reader.read_u8();
+        self.ppu_ctrl2 = reader.read_u8();
+        self.ppu_status = reader.read_u8();
+        self.oam.load(reader);
+        self.loopy.load(reader);
+        self.ppu_data.load(reader);
     }
 }
 
@@ -172,6 +197,14 @@ impl PpuRegisters for Registers {
         self.ppu_status |= 0x40;
     }
 
+    fn clear_sprite_overflow(&mut self) {
+        self.ppu_status &= 0xDF;
+    }
+
+    fn set_sprite_overflow(&mut self) {
+        self.ppu_status |= 0x20;
+    }
+
     fn get_ppu_addr_increment_value(&self) -> usize {
         if self.ppu_ctrl1 & 0x04 == 0x04 {
             32
@@ -200,18 +233,6 @@ impl PpuRegisters for Registers {
         }
     }
 
-    fn get_name_table_id(&self) -> Data {
-        self.ppu_ctrl1 & 0x03
-    }
-
-    fn get_scroll_x(&self) -> Data {
-        self.ppu_scroll.get_x()
-    }
-
-    fn get_scroll_y(&self) -> Data {
-        self.ppu_scroll.get_y()
-    }
-
     fn is_background_enable(&self) -> bool {
         self.ppu_ctrl2 & 0x08 == 0x08
     }
@@ -228,16 +249,31 @@ impl PpuRegisters for Registers {
         self.ppu_ctrl2 & 0x04 == 0x04
     }
 
-    fn read<P: PaletteRam>(&mut self, addr: Addr, ctx: &mut PpuCtx<P>) -> Data {
+    fn is_greyscale(&self) -> bool {
+        self.ppu_ctrl2 & 0x01 == 0x01
+    }
+
+    // bit 0: red, 1: green, 2: blue
+    fn get_emphasis(&self) -> Data {
+        self.ppu_ctrl2 >> 5
+    }
+
+    fn read<P: PaletteRam>(&mut self, addr: Addr, ctx: &mut PpuCtx<P>, mapper: &dyn Mapper) -> Data {
         match addr {
             0x0002 => self.read_status(),
             0x0004 => self.oam.read_data(&ctx.sprite_ram),
-            0x0007 => self.read_ppu_data(&ctx.vram, &ctx.cram, &ctx.palette),
+            0x0007 => self.read_ppu_data(&ctx.vram, &ctx.palette, mapper),
             _ => 0,
         }
     }
 
-    fn write<P: PaletteRam>(&mut self, addr: Addr, data: Data, ctx: &mut PpuCtx<P>) {
+    fn write<P: PaletteRam>(
+        &mut self,
+        addr: Addr,
+        data: Data,
+        ctx: &mut PpuCtx<P>,
+        mapper: &mut dyn Mapper,
+    ) {
         match addr {
             /*
                 Control Register1 0x2000
@@ -254,15 +290,14 @@ impl PpuRegisters for Registers {
               |      |            0x02: 0x2800                     |
               |      |            0x03: 0x2C00                     |
               */
-            0x0000 => self.ppu_ctrl1 = data,
+            0x0000 => self.write_ppu_ctrl1(data),
             /*
                Control Register2 0x2001
              | bit  | description                                 |
              +------+---------------------------------------------+
-             |  7-5 | Background color  0x00: Black               |
-             |      |                   0x01: Green               |
-             |      |                   0x02: Blue                |
-             |      |                   0x04: Red                 |
+             |  7   | Emphasize blue                              |
+             |  6   | Emphasize green                             |
+             |  5   | Emphasize red                               |
              |  4   | Enable sprite                               |
              |  3   | Enable background                           |
              |  2   | Sprite mask       render left end           |
@@ -281,9 +316,9 @@ impl PpuRegisters for Registers {
             0x0001 => self.ppu_ctrl2 = data,
             0x0003 => self.write_oam_addr(data),
             0x0004 => self.write_oam_data(data, &mut ctx.sprite_ram),
-            0x0005 => self.ppu_scroll.write(data),
-            0x0006 => self.write_ppu_addr(data),
-            0x0007 => self.write_ppu_data(data, &mut ctx.vram, &mut ctx.cram, &mut ctx.palette),
+            0x0005 => self.loopy.write_scroll(data),
+            0x0006 => self.loopy.write_addr(data),
+            0x0007 => self.write_ppu_data(data, &mut ctx.vram, &mut ctx.palette, mapper),
             _ => (),
         }
     }
@@ -1,5 +1,6 @@
 use super::super::super::types::{Data, Addr};
 use super::super::super::ram::*;
+use super::super::super::state::{Snapshot, StateReader, StateWriter};
 
 #[derive(Debug)]
 pub struct Oam {
@@ -47,6 +48,16 @@ impl Oam {
     }
 }
 
+impl Snapshot for Oam {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.addr);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.addr = reader.read_u16();
+    }
+}
+
 
 #[test]
 fn set_addr() {
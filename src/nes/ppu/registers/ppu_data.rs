@@ -1,6 +1,9 @@
 use super::super::super::types::{Data, Addr};
+use super::super::super::mapper::{Mapper, Mirroring};
 use super::super::super::Ram;
 use super::super::palette::*;
+use super::super::sprite_utils::mirror_down_name_table_addr;
+use super::super::super::state::{Snapshot, StateReader, StateWriter};
 
 #[derive(Debug)]
 pub struct PpuData {
@@ -17,50 +20,62 @@ impl PpuData {
         PpuData { buf: 0 }
     }
 
-    pub fn read<P: PaletteRam>(&mut self, vram: &Ram, cram: &Ram, addr: Addr, palette: &P) -> Data {
+    pub fn read<P: PaletteRam>(
+        &mut self,
+        vram: &Ram,
+        addr: Addr,
+        palette: &P,
+        mapper: &dyn Mapper,
+    ) -> Data {
         let buf = self.buf;
-        // println!("vram cpu read0 {:X}", addr);
+        let addr = addr & 0x3FFF;
+        // Reading palette data from $3F00-$3FFF works differently.
+        // The palette data is placed immediately on the data bus, and hence no dummy read is required.
+        // Reading the palettes still updates the internal buffer though, but the data placed in it is the mirrored nametable data
+        // that would appear "underneath" the palette. (Checking the PPU memory map should make this clearer.)
+        if addr >= 0x3F00 {
+            self.buf = vram.read(self.calc_addr(addr, mapper.get_mirroring()));
+            return palette.read((addr - 0x3F00) & 0x1F);
+        }
         if addr >= 0x2000 {
-            let addr = self.calc_addr(addr);
-            // Reading palette data from $3F00-$3FFF works differently.
-            // The palette data is placed immediately on the data bus, and hence no dummy read is required.
-            // Reading the palettes still updates the internal buffer though, but the data placed in it is the mirrored nametable data
-            // that would appear "underneath" the palette. (Checking the PPU memory map should make this clearer.)
-            if addr >= 0x3F00 {
-                // println!("vram cpu read {:X}", addr);
-                self.buf = vram.read(addr);
-                return palette.read(addr - 0x3f00);
-            }
-            self.buf = vram.read(addr);
+            self.buf = vram.read(self.calc_addr(addr, mapper.get_mirroring()));
         } else {
-            self.buf = cram.read(addr);
+            self.buf = mapper.read_character(addr);
         }
         buf
     }
 
-    pub fn write<P: PaletteRam>(&mut self,
-                                vram: &mut Ram,
-                                cram: &mut Ram,
-                                addr: Addr,
-                                data: Data,
-                                palette: &mut P) {
-        if addr >= 0x2000 {
-            if addr >= 0x3f00 && addr < 0x4000 {
-                palette.write(addr - 0x3f00, data);
-            } else {
-                let addr = self.calc_addr(addr);
-                vram.write(addr, data);
-            }
+    pub fn write<P: PaletteRam>(
+        &mut self,
+        vram: &mut Ram,
+        addr: Addr,
+        data: Data,
+        palette: &mut P,
+        mapper: &mut dyn Mapper,
+    ) {
+        let addr = addr & 0x3FFF;
+        if addr >= 0x3F00 {
+            palette.write(addr - 0x3F00, data);
+        } else if addr >= 0x2000 {
+            let addr = self.calc_addr(addr, mapper.get_mirroring());
+            vram.write(addr, data);
         } else {
-            cram.write(addr, data);
+            mapper.write_character(addr, data);
         }
     }
 
-    fn calc_addr(&self, addr: Addr) -> Addr {
-        if addr >= 0x3000 && addr < 0x3f00 {
-            addr - 0x3000
-        } else {
-            addr - 0x2000
-        }
+    // 0x3000-0x3EFF is a mirror of 0x2000-0x2EFF.
+    fn calc_addr(&self, addr: Addr, mirroring: Mirroring) -> Addr {
+        mirror_down_name_table_addr(addr - 0x2000, mirroring)
+    }
+}
+
+impl Snapshot for PpuData {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.buf);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        self.buf = reader.read_u8();
     }
 }
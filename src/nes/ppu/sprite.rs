@@ -1,58 +1,229 @@
-use self::super::palette::*;
-use self::super::sprite_utils::*;
-use self::super::Mmc;
-use self::super::Ram;
+use super::super::mapper::Mapper;
+use super::super::types::{Addr, Data};
+use super::Ram;
 
-const SPRITES_NUMBER: u16 = 0x100;
+const SPRITES_NUMBER: usize = 64;
+// Secondary OAM holds up to 8 sprites for a line.
+const SPRITES_PER_LINE: usize = 8;
 
-pub type SpritesWithCtx = Vec<SpriteWithCtx>;
+// Sprite attribute (OAM byte 2)
+//
+// | bit | description                              |
+// +-----+------------------------------------------+
+// | 7   | flip vertically                          |
+// | 6   | flip horizontally                        |
+// | 5   | priority 0: in front of bg, 1: behind bg |
+// | 1-0 | palette                                  |
+#[derive(Debug)]
+struct LineSprite {
+    x: Data,
+    attr: Data,
+    pattern_low: Data,
+    pattern_high: Data,
+    is_zero: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SpritePixel {
+    // Palette entry 0x10-0x1F.
+    pub entry: Data,
+    pub is_behind_background: bool,
+    pub is_zero: bool,
+}
 
+// Sprites found on the next line with their patterns.
 #[derive(Debug)]
-pub struct SpriteWithCtx {
-    pub sprite: Sprite,
-    pub position: SpritePosition,
-    pub attr: u8,
-    pub palette: PaletteList,
+pub struct Sprites {
+    sprites: Vec<LineSprite>,
+    // Draws every sprite on the line to reduce flicker, the overflow flag is not affected.
+    is_unlimited: bool,
 }
 
-pub fn build_sprites<P: PaletteRam>(
-    cram: &Ram,
-    sprite_ram: &Ram,
-    palette: &P,
-    offset: u16,
-    is_8x8: bool,
-    mmc: &Mmc,
-) -> SpritesWithCtx {
-    let mut buf: SpritesWithCtx = vec![];
-    for i in 0..(SPRITES_NUMBER / 4) {
-        // INFO: Offset sprite Y position, because First and last 8line is not rendered.
-        let base = i * 4;
-        let y = sprite_ram.read(base);
-        if y >= 8 && y < 224 {
-            let sprite_id = sprite_ram.read(base + 1);
+impl Sprites {
+    pub fn new() -> Self {
+        Sprites {
+            sprites: Vec::new(),
+            is_unlimited: false,
+        }
+    }
+
+    pub fn set_unlimited(&mut self, is_unlimited: bool) {
+        self.is_unlimited = is_unlimited;
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    // Sprites are delayed by one line, the Y position in OAM is the line before the top.
+    // Returns the sprite overflow flag.
+    pub fn evaluate(
+        &mut self,
+        line: usize,
+        sprite_ram: &Ram,
+        height: usize,
+        table_offset: Addr,
+        mapper: &dyn Mapper,
+    ) -> bool {
+        self.sprites.clear();
+        let (found, is_overflow) = find_sprites(line, sprite_ram, height, self.is_unlimited);
+        for i in found {
+            let base = (i * 4) as Addr;
+            let y = sprite_ram.read(base) as usize;
+            let id = sprite_ram.read(base + 1);
             let attr = sprite_ram.read(base + 2);
-            let (offset, sprite_id) = if is_8x8 {
-                (offset, sprite_id)
+            let row = if attr & 0x80 == 0x80 {
+                height - 1 - (line - y)
+            } else {
+                line - y
+            };
+            let addr = get_pattern_addr(id, row, height, table_offset);
+            let (pattern_low, pattern_high) = if attr & 0x40 == 0x40 {
+                (
+                    mapper.read_character(addr).reverse_bits(),
+                    mapper.read_character(addr + 8).reverse_bits(),
+                )
             } else {
-                // 76543210
-                // ||||||||
-                // |||||||+- Bank ($0000 or $1000) of tiles
-                // +++++++-- Tile number of top of sprite (0 to 254; bottom half gets the next tile)
-                let offset = 0x1000u16 * (sprite_id & 0x01) as u16;
-                let sprite_id = sprite_id & 0xFE;
-                (offset, sprite_id)
+                (mapper.read_character(addr), mapper.read_character(addr + 8))
             };
-            let x = sprite_ram.read(base + 3);
-            let sprite = build(&cram, sprite_id as u8, offset, &mmc, is_8x8);
-            let position: SpritePosition = (x, y - 8);
-            let palette_id = attr & 0x03;
-            buf.push(SpriteWithCtx {
-                sprite,
-                position,
+            self.sprites.push(LineSprite {
+                x: sprite_ram.read(base + 3),
                 attr,
-                palette: palette.get(palette_id, PaletteType::Sprite),
+                pattern_low,
+                pattern_high,
+                is_zero: i == 0,
             });
         }
+        is_overflow
+    }
+
+    // The first opaque sprite in OAM order wins, even if it is behind the background.
+    pub fn get_pixel(&self, x: usize) -> Option<SpritePixel> {
+        self.sprites.iter().find_map(|sprite| {
+            let offset = x.wrapping_sub(sprite.x as usize);
+            if offset >= 8 {
+                return None;
+            }
+            let mask = 0x80 >> offset;
+            let low = if sprite.pattern_low & mask != 0 { 1 } else { 0 };
+            let high = if sprite.pattern_high & mask != 0 { 2 } else { 0 };
+            if low | high == 0 {
+                return None;
+            }
+            Some(SpritePixel {
+                entry: 0x10 | (sprite.attr & 0x03) << 2 | low | high,
+                is_behind_background: sprite.attr & 0x20 == 0x20,
+                is_zero: sprite.is_zero,
+            })
+        })
+    }
+}
+
+impl Default for Sprites {
+    fn default() -> Self {
+        Sprites::new()
+    }
+}
+
+fn is_in_range(line: usize, y: Data, height: usize) -> bool {
+    let y = y as usize;
+    line >= y && line < y + height
+}
+
+// Returns the OAM indices of the sprites on the line and the overflow flag.
+//
+// After 8 sprites are found, the hardware keeps looking for a 9th sprite to set the flag
+// but increments the byte offset together with the sprite index, so that
+// the tile number, attribute or X position is taken as Y.
+// This gives both false positives and false negatives, which games rely on.
+fn find_sprites(
+    line: usize,
+    sprite_ram: &Ram,
+    height: usize,
+    is_unlimited: bool,
+) -> (Vec<usize>, bool) {
+    let mut found = Vec::new();
+    let mut n = 0;
+    while n < SPRITES_NUMBER && found.len() < SPRITES_PER_LINE {
+        if is_in_range(line, sprite_ram.read((n * 4) as Addr), height) {
+            found.push(n);
+        }
+        n += 1;
+    }
+    let mut is_overflow = false;
+    let mut m = 0;
+    for i in n..SPRITES_NUMBER {
+        let y = sprite_ram.read((i * 4 + m) as Addr);
+        if is_in_range(line, y, height) {
+            is_overflow = true;
+            break;
+        }
+        m = (m + 1) % 4;
+    }
+    if is_unlimited {
+        found.extend((n..SPRITES_NUMBER).filter(|&i| {
+            is_in_range(line, sprite_ram.read((i * 4) as Addr), height)
+        }));
+    }
+    (found, is_overflow)
+}
+
+// 8x16 sprites take the pattern table from bit0 of the tile number,
+// the top half is the even tile and the bottom half is the next one.
+fn get_pattern_addr(id: Data, row: usize, height: usize, table_offset: Addr) -> Addr {
+    let (table_offset, id) = if height == 8 {
+        (table_offset, id)
+    } else {
+        let bank = 0x1000 * (id & 0x01) as Addr;
+        (bank, (id & 0xFE) + if row >= 8 { 1 } else { 0 })
+    };
+    table_offset + id as Addr * 16 + (row % 8) as Addr
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_pattern_addr() {
+        assert_eq!(get_pattern_addr(0x02, 3, 8, 0x1000), 0x1023);
+        assert_eq!(get_pattern_addr(0x03, 3, 16, 0x0000), 0x1023);
+        assert_eq!(get_pattern_addr(0x03, 10, 16, 0x0000), 0x1032);
+    }
+
+    fn create_sprite_ram(ys: &[Data]) -> Ram {
+        // Sprites off the screen by default, tile, attribute and X are 0xFF as well.
+        let mut field = vec![0xFF; SPRITES_NUMBER * 4];
+        for (i, &y) in ys.iter().enumerate() {
+            field[i * 4] = y;
+        }
+        Ram::new(field)
+    }
+
+    #[test]
+    fn test_find_sprites_up_to_8() {
+        let ram = create_sprite_ram(&[0x10; 10]);
+        let (found, is_overflow) = find_sprites(0x12, &ram, 8, false);
+        assert_eq!(found, (0..8).collect::<Vec<_>>());
+        assert!(is_overflow);
+        let (found, _) = find_sprites(0x12, &ram, 8, true);
+        assert_eq!(found.len(), 10);
+        let (found, is_overflow) = find_sprites(0x20, &ram, 8, false);
+        assert!(found.is_empty());
+        assert!(!is_overflow);
+    }
+
+    #[test]
+    fn test_overflow_bug() {
+        // The 10th sprite is on the line but the flag is taken from its tile number.
+        let mut ram = create_sprite_ram(&[0x10; 8]);
+        ram.write(9 * 4, 0x10);
+        ram.write(9 * 4 + 1, 0x40);
+        assert!(!find_sprites(0x12, &ram, 8, false).1);
+        // Nothing is on the line after the 8th, but the tile number of the 10th looks like Y.
+        let mut ram = create_sprite_ram(&[0x10; 8]);
+        ram.write(8 * 4, 0x80);
+        ram.write(9 * 4 + 1, 0x0E);
+        assert!(find_sprites(0x12, &ram, 8, false).1);
     }
-    buf
 }
@@ -1,3 +1,5 @@
+use nes::state::{Snapshot, StateReader, StateWriter};
+
 #[derive(Debug)]
 pub struct Ram {
     pub field: Vec<u8>,
@@ -16,3 +18,13 @@ impl Ram {
         self.field[addr as usize] = data;
     }
 }
+
+impl Snapshot for Ram {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.field);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) {
+        reader.read_bytes(&mut self.field);
+    }
+}
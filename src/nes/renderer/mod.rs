@@ -1,122 +1,128 @@
 mod color;
 
-use super::{BackgroundField, BackgroundCtx};
-use super::PaletteList;
-use super::{Sprite, SpritesWithCtx, SpritePosition};
 use self::color::COLORS;
+use super::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
-extern "C" {
-    fn canvas_render(ptr: *const u8, len: usize);
-}
+// Each emphasis bit of $2001 darkens the other two channels.
+const EMPHASIS_ATTENUATION: f32 = 0.816;
 
 #[derive(Debug)]
 pub struct Renderer {
     buf: Vec<u8>,
+    // RGB of the 64 palette colors for each of the 8 emphasis combinations.
+    colors: Vec<(u8, u8, u8)>,
 }
 
-
 impl Renderer {
     pub fn new() -> Self {
-        Renderer { buf: vec![0xFF; 256 * 224 * 4] }
+        Renderer {
+            buf: vec![0xFF; SCREEN_WIDTH * SCREEN_HEIGHT * 4],
+            colors: build_colors(),
+        }
     }
 
-    pub fn render(&mut self, background: &BackgroundField, sprites: &SpritesWithCtx) {
-        self.render_background(background);
-        self.render_sprites(sprites, background);
-        unsafe {
-            canvas_render(self.buf.as_ptr(), self.buf.len());
+    // Converts the palette colors of the PPU frame to RGBA.
+    pub fn render(&mut self, frame: &[u16]) {
+        for (i, pixel) in frame.iter().enumerate() {
+            let color = self.colors[*pixel as usize & 0x1FF];
+            let index = i * 4;
+            self.buf[index] = color.0;
+            self.buf[index + 1] = color.1;
+            self.buf[index + 2] = color.2;
         }
     }
 
-    fn should_pixel_hide(&self, x: usize, y: usize, background: &BackgroundField) -> bool {
-        let tile_x = x / 8;
-        let tile_y = y / 8;
-        let background_index = tile_y * 33 + tile_x;
-        let sprite = &background[background_index];
-        // NOTE: If background pixel is not transparent, we need to hide sprite.
-        (sprite.tile.sprite[y % 8][x % 8] % 4) != 0
+    // RGBA pixels of 256x240, use `Overscan` to hide the edges as TVs do.
+    pub fn get_buf(&self) -> &[u8] {
+        &self.buf
     }
+}
 
-    fn render_background(&mut self, background: &BackgroundField) {
-        for (i, bg) in background.into_iter().enumerate() {
-            if bg.is_enabled {
-                let x = (i % 33) * 8;
-                let y = (i / 33) * 8;
-                self.render_tile(bg, x, y);
-            }
-        }
+// Pixels to hide on each edge of the frame.
+// NTSC TVs hide about 8 lines at the top and bottom, games often leave garbage there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overscan {
+    pub top: usize,
+    pub bottom: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+impl Overscan {
+    pub fn none() -> Self {
+        Overscan { top: 0, bottom: 0, left: 0, right: 0 }
     }
 
-    fn render_sprites(&mut self, sprites: &SpritesWithCtx, background: &BackgroundField) {
-        for sprite in sprites {
-            self.render_sprite(&sprite.sprite,
-                               &sprite.position,
-                               &sprite.palette,
-                               sprite.attr,
-                               &background);
-        }
+    pub fn ntsc() -> Self {
+        Overscan { top: 8, bottom: 8, left: 0, right: 0 }
+    }
+
+    // Returns (width, height) of the cropped frame.
+    pub fn get_size(&self) -> (usize, usize) {
+        (
+            SCREEN_WIDTH.saturating_sub(self.left + self.right),
+            SCREEN_HEIGHT.saturating_sub(self.top + self.bottom),
+        )
     }
 
-    fn render_sprite(&mut self,
-                     sprite: &Sprite,
-                     position: &SpritePosition,
-                     palette: &PaletteList,
-                     attr: u8,
-                     background: &BackgroundField) {
-        let is_vertical_reverse = (attr & 0x80) == 0x80;
-        let is_horizontal_reverse = (attr & 0x40) == 0x40;
-        let is_low_priority = (attr & 0x20) == 0x20;
-        let h = sprite.len();
-        for i in 0..h {
-            let y = position.1 as usize + if is_vertical_reverse { h - 1 - i } else { i };
-            if y >= 224 {
-                continue;
-            }
-            for j in 0..8 {
-                let x = position.0 as usize + if is_horizontal_reverse { 7 - j } else { j };
-                if x >= 256 {
-                    continue;
-                }
-                if is_low_priority && self.should_pixel_hide(x, y, background) {
-                    continue;
-                }
-                if sprite[i][j] != 0 {
-                    let color_id = palette[sprite[i][j] as usize];
-                    let color = COLORS[color_id as usize];
-                    let index = (x + (y * 0x100)) * 4;
-                    self.buf[index] = color.0;
-                    self.buf[index + 1] = color.1;
-                    self.buf[index + 2] = color.2;
-                    // TODO: See register value weather clip or not.
-                    if x < 8 {
-                        self.buf[index + 3] = 0;
-                    }
-                }
-            }
+    // Crops the RGBA frame of `Renderer::get_buf`.
+    pub fn crop(&self, buf: &[u8]) -> Vec<u8> {
+        let (width, height) = self.get_size();
+        let mut cropped = Vec::with_capacity(width * height * 4);
+        for line in buf.chunks(SCREEN_WIDTH * 4).skip(self.top).take(height) {
+            cropped.extend_from_slice(&line[self.left * 4..(self.left + width) * 4]);
         }
+        cropped
     }
+}
 
+fn build_colors() -> Vec<(u8, u8, u8)> {
+    let mut colors = Vec::with_capacity(8 * COLORS.len());
+    for emphasis in 0..8 {
+        let attenuate = |value: u8, channel: usize| {
+            let count = (0..3)
+                .filter(|&bit| bit != channel && emphasis & (1 << bit) != 0)
+                .count();
+            (value as f32 * EMPHASIS_ATTENUATION.powi(count as i32)) as u8
+        };
+        colors.extend(
+            COLORS
+                .iter()
+                .map(|&(r, g, b)| (attenuate(r, 0), attenuate(g, 1), attenuate(b, 2))),
+        );
+    }
+    colors
+}
 
-    fn render_tile(&mut self, bg: &BackgroundCtx, x: usize, y: usize) {
-        let offset_x = (bg.scroll_x % 8) as i32;
-        let offset_y = (bg.scroll_y % 8) as i32;
-        for i in 0..8 {
-            for j in 0..8 {
-                let x = (x + j) as i32 - offset_x;
-                let y = (y + i) as i32 - offset_y;
-                if x >= 0 as i32 && 0xFF >= x && y >= 0 as i32 && y < 224 {
-                    let color_id = bg.tile.palette[bg.tile.sprite[i][j] as usize];
-                    let color = COLORS[color_id as usize];
-                    let index = ((x + (y * 0x100)) * 4) as usize;
-                    self.buf[index] = color.0;
-                    self.buf[index + 1] = color.1;
-                    self.buf[index + 2] = color.2;
-                    // TODO: See register value weather clip or not.
-                    if x < 8 {
-                        self.buf[index + 3] = 0;
-                    }
-                }
-            }
-        }
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_emphasis_darkens_other_channels() {
+        let colors = build_colors();
+        assert_eq!(colors[0x30], COLORS[0x30]);
+        // Red emphasis keeps red of the white.
+        let (r, g, b) = colors[0x040 | 0x30];
+        assert_eq!(r, COLORS[0x30].0);
+        assert!(g < COLORS[0x30].1 && b < COLORS[0x30].2);
+        // All of them darken every channel.
+        let (r, g, b) = colors[0x1C0 | 0x30];
+        assert!(r < COLORS[0x30].0 && g < COLORS[0x30].1 && b < COLORS[0x30].2);
+    }
+
+    #[test]
+    fn test_crop_overscan() {
+        let buf: Vec<u8> = (0..SCREEN_WIDTH * SCREEN_HEIGHT)
+            .flat_map(|i| vec![(i % SCREEN_WIDTH) as u8, (i / SCREEN_WIDTH) as u8, 0, 0xFF])
+            .collect();
+        assert_eq!(Overscan::none().crop(&buf), buf);
+        let overscan = Overscan { top: 8, bottom: 16, left: 4, right: 2 };
+        assert_eq!(overscan.get_size(), (250, 216));
+        let cropped = overscan.crop(&buf);
+        assert_eq!(cropped.len(), 250 * 216 * 4);
+        assert_eq!(&cropped[0..2], &[4, 8]);
+        let last = cropped.len() - 4;
+        assert_eq!(&cropped[last..last + 2], &[253, 223]);
     }
-}
\ No newline at end of file
+}
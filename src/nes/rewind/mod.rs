@@ -0,0 +1,168 @@
+mod rle;
+
+use nes::Context;
+use std::collections::VecDeque;
+
+// A new keyframe is taken after this many snapshots so deltas stay small.
+const KEYFRAME_INTERVAL: usize = 60;
+
+#[derive(Debug)]
+enum Entry {
+    Keyframe(Vec<u8>),
+    // XOR against the latest keyframe before it.
+    Delta(Vec<u8>),
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        match *self {
+            Entry::Keyframe(ref buf) | Entry::Delta(ref buf) => buf.len(),
+        }
+    }
+
+    fn is_keyframe(&self) -> bool {
+        matches!(*self, Entry::Keyframe(_))
+    }
+}
+
+// Ring of compressed snapshots to step backwards through.
+// Call `push` after every `nes::run` and `step_back` to restore the previous snapshot.
+// The oldest snapshots are dropped, keyframe by keyframe, to stay within the memory budget.
+#[derive(Debug)]
+pub struct Rewind {
+    interval: usize,
+    memory_budget: usize,
+    snapshots: VecDeque<Entry>,
+    // Uncompressed copy of the keyframe the next delta is taken against.
+    keyframe: Vec<u8>,
+    frames: usize,
+    memory_usage: usize,
+    is_latest_current: bool,
+    should_take_keyframe: bool,
+}
+
+impl Rewind {
+    // Takes a snapshot every `interval` frames and keeps up to `memory_budget` bytes of them.
+    pub fn new(interval: usize, memory_budget: usize) -> Self {
+        Rewind {
+            interval: interval.max(1),
+            memory_budget,
+            snapshots: VecDeque::new(),
+            keyframe: Vec::new(),
+            frames: 0,
+            memory_usage: 0,
+            is_latest_current: false,
+            should_take_keyframe: true,
+        }
+    }
+
+    pub fn push(&mut self, ctx: &Context) {
+        self.frames += 1;
+        if self.frames < self.interval {
+            self.is_latest_current = false;
+            return;
+        }
+        self.frames = 0;
+        let state = ctx.save_state();
+        let snapshot = if self.should_take_keyframe || state.len() != self.keyframe.len() {
+            let snapshot = Entry::Keyframe(rle::compress(&state));
+            self.keyframe = state;
+            snapshot
+        } else {
+            Entry::Delta(rle::compress(&xor(&state, &self.keyframe)))
+        };
+        self.memory_usage += snapshot.size();
+        self.snapshots.push_back(snapshot);
+        self.should_take_keyframe = self.count_since_keyframe() >= KEYFRAME_INTERVAL;
+        self.is_latest_current = true;
+        self.shrink();
+    }
+
+    // Restores the snapshot before the current frame.
+    // Returns false when there is nothing older to go back to.
+    pub fn step_back(&mut self, ctx: &mut Context) -> bool {
+        if self.is_latest_current {
+            if self.snapshots.len() < 2 {
+                return false;
+            }
+            self.pop_back();
+        }
+        let state = match self.snapshots.back() {
+            Some(Entry::Keyframe(_)) => self.keyframe.clone(),
+            Some(Entry::Delta(buf)) => xor(&rle::decompress(buf), &self.keyframe),
+            None => return false,
+        };
+        ctx.load_state(&state)
+            .expect("Rewind snapshot is not compatible with the context.");
+        self.is_latest_current = true;
+        self.frames = 0;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.keyframe.clear();
+        self.memory_usage = 0;
+        self.is_latest_current = false;
+        self.should_take_keyframe = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn get_memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    fn count_since_keyframe(&self) -> usize {
+        self.snapshots.iter().rev().take_while(|s| !s.is_keyframe()).count()
+    }
+
+    fn pop_back(&mut self) {
+        if let Some(snapshot) = self.snapshots.pop_back() {
+            self.memory_usage -= snapshot.size();
+            if snapshot.is_keyframe() {
+                // Deltas before it refer to the previous keyframe.
+                self.keyframe = self.find_latest_keyframe();
+            }
+        }
+        self.should_take_keyframe =
+            self.snapshots.is_empty() || self.count_since_keyframe() >= KEYFRAME_INTERVAL;
+    }
+
+    fn find_latest_keyframe(&self) -> Vec<u8> {
+        match self.snapshots.iter().rev().find(|s| s.is_keyframe()) {
+            Some(Entry::Keyframe(buf)) => rle::decompress(buf),
+            _ => Vec::new(),
+        }
+    }
+
+    // Deltas cannot be restored without their keyframe, so the oldest keyframe is dropped
+    // together with its deltas. The latest group is kept, a new keyframe is requested instead.
+    fn shrink(&mut self) {
+        while self.memory_usage > self.memory_budget {
+            let group_len = 1 + self
+                .snapshots
+                .iter()
+                .skip(1)
+                .take_while(|s| !s.is_keyframe())
+                .count();
+            if group_len == self.snapshots.len() {
+                self.should_take_keyframe = true;
+                return;
+            }
+            for snapshot in self.snapshots.drain(0..group_len) {
+                self.memory_usage -= snapshot.size();
+            }
+        }
+    }
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b.iter()).map(|(x, y)| x ^ y).collect()
+}
@@ -0,0 +1,78 @@
+// Run length encoding tuned for save state deltas, which are mostly zeros.
+//
+// | control   | description                                 |
+// +-----------+---------------------------------------------+
+// | 0x00-0x7F | (control + 1) literal bytes follow          |
+// | 0x80-0xFF | next byte is repeated (control - 0x7E) times |
+const MAX_LITERAL: usize = 0x80;
+const MIN_RUN: usize = 2;
+const MAX_RUN: usize = 0x81;
+
+pub fn compress(src: &[u8]) -> Vec<u8> {
+    let mut dest = Vec::new();
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i < src.len() {
+        let run = src[i..].iter().take(MAX_RUN).take_while(|&&b| b == src[i]).count();
+        if run >= MIN_RUN {
+            flush_literal(&src[literal_start..i], &mut dest);
+            dest.push((run - MIN_RUN) as u8 | 0x80);
+            dest.push(src[i]);
+            i += run;
+            literal_start = i;
+        } else {
+            i += 1;
+        }
+    }
+    flush_literal(&src[literal_start..], &mut dest);
+    dest
+}
+
+fn flush_literal(literal: &[u8], dest: &mut Vec<u8>) {
+    for chunk in literal.chunks(MAX_LITERAL) {
+        dest.push((chunk.len() - 1) as u8);
+        dest.extend_from_slice(chunk);
+    }
+}
+
+pub fn decompress(src: &[u8]) -> Vec<u8> {
+    let mut dest = Vec::new();
+    let mut i = 0;
+    while i < src.len() {
+        let control = src[i] as usize;
+        if control & 0x80 == 0x80 {
+            let run = (control & 0x7F) + MIN_RUN;
+            let len = dest.len();
+            dest.resize(len + run, src[i + 1]);
+            i += 2;
+        } else {
+            let len = control + 1;
+            dest.extend_from_slice(&src[i + 1..i + 1 + len]);
+            i += 1 + len;
+        }
+    }
+    dest
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut src = vec![0; 1000];
+        src[10] = 1;
+        src[11] = 2;
+        src[500] = 0xFF;
+        src.extend((0..300).map(|i| i as u8));
+        let compressed = compress(&src);
+        assert!(compressed.len() < 400);
+        assert_eq!(decompress(&compressed), src);
+    }
+
+    #[test]
+    fn test_empty_and_single_byte() {
+        assert_eq!(decompress(&compress(&[])), vec![]);
+        assert_eq!(decompress(&compress(&[7])), vec![7]);
+    }
+}
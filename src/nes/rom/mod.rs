@@ -5,11 +5,11 @@ pub struct Rom {
 
 impl Rom {
     pub fn new(buf: Vec<u8>) -> Rom {
-        Rom { vec: buf.clone() }
+        Rom { vec: buf }
     }
 
-    pub fn read(&self, addr: u16) -> u8 {
-        self.vec[addr as usize]
+    pub fn read(&self, addr: usize) -> u8 {
+        self.vec[addr]
     }
 
     pub fn size(&self) -> usize {
@@ -0,0 +1,232 @@
+use std::fmt;
+
+// Save state layout (little endian)
+//
+// | offset | size | description                          |
+// +--------+------+--------------------------------------+
+// | 0x00   | 4    | magic "RNES"                         |
+// | 0x04   | 4    | format version                       |
+// | 0x08   | 8    | FNV-1a hash of the *.nes file        |
+// | 0x10   | 4    | payload length                       |
+// | 0x14   | 4    | FNV-1a checksum of the payload       |
+// | 0x18   | -    | payload written by each component    |
+const MAGIC: &[u8; 4] = b"RNES";
+pub const STATE_VERSION: u32 = 8;
+const HEADER_SIZE: usize = 0x18;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    RomMismatch,
+    Corrupted,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::InvalidMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(v) => {
+                write!(f, "unsupported save state version {} (expected {})", v, STATE_VERSION)
+            }
+            StateError::RomMismatch => write!(f, "save state was taken with another rom"),
+            StateError::Corrupted => write!(f, "save state is corrupted"),
+        }
+    }
+}
+
+// Implemented by every component holding state that survives between frames.
+// `load` has to read exactly what `save` wrote, in the same order.
+pub trait Snapshot {
+    fn save(&self, writer: &mut StateWriter);
+
+    fn load(&mut self, reader: &mut StateReader);
+}
+
+pub fn hash(buf: &[u8]) -> u64 {
+    buf.iter().fold(FNV_OFFSET_BASIS, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn checksum(buf: &[u8]) -> u32 {
+    let hash = hash(buf);
+    (hash ^ (hash >> 32)) as u32
+}
+
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        StateWriter { buf: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn write_bool(&mut self, v: bool) {
+        self.write_u8(v as u8);
+    }
+
+    pub fn write_u16(&mut self, v: u16) {
+        self.write_u8(v as u8);
+        self.write_u8((v >> 8) as u8);
+    }
+
+    pub fn write_u32(&mut self, v: u32) {
+        self.write_u16(v as u16);
+        self.write_u16((v >> 16) as u16);
+    }
+
+    pub fn write_u64(&mut self, v: u64) {
+        self.write_u32(v as u32);
+        self.write_u32((v >> 32) as u32);
+    }
+
+    // usize is stored as 64bit so that states are portable between wasm and native builds.
+    pub fn write_usize(&mut self, v: usize) {
+        self.write_u64(v as u64);
+    }
+
+    pub fn write_bytes(&mut self, v: &[u8]) {
+        self.write_u32(v.len() as u32);
+        self.buf.extend_from_slice(v);
+    }
+
+    // Wraps the payload with the header.
+    pub fn finish(self, rom_hash: u64) -> Vec<u8> {
+        let mut header = StateWriter::new();
+        header.buf.extend_from_slice(MAGIC);
+        header.write_u32(STATE_VERSION);
+        header.write_u64(rom_hash);
+        header.write_u32(self.buf.len() as u32);
+        header.write_u32(checksum(&self.buf));
+        header.buf.extend_from_slice(&self.buf);
+        header.buf
+    }
+}
+
+#[derive(Debug)]
+pub struct StateReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    // Validates the header and returns a reader over the payload.
+    // Nothing is restored unless the whole state is intact.
+    pub fn open(buf: &'a [u8], rom_hash: u64) -> Result<StateReader<'a>, StateError> {
+        if buf.len() < HEADER_SIZE || &buf[0..4] != MAGIC {
+            return Err(StateError::InvalidMagic);
+        }
+        let mut header = StateReader { buf: &buf[4..HEADER_SIZE], pos: 0 };
+        let version = header.read_u32();
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        if header.read_u64() != rom_hash {
+            return Err(StateError::RomMismatch);
+        }
+        let len = header.read_u32() as usize;
+        let payload = &buf[HEADER_SIZE..];
+        if payload.len() != len || header.read_u32() != checksum(payload) {
+            return Err(StateError::Corrupted);
+        }
+        Ok(StateReader { buf: payload, pos: 0 })
+    }
+
+    // The payload is checked before reading, so running past the end only happens
+    // when a component reads more than it wrote. Zeros are returned in that case.
+    pub fn read_u8(&mut self) -> u8 {
+        let v = self.buf.get(self.pos).cloned().unwrap_or(0);
+        self.pos += 1;
+        v
+    }
+
+    pub fn read_bool(&mut self) -> bool {
+        self.read_u8() != 0
+    }
+
+    pub fn read_u16(&mut self) -> u16 {
+        let lower = u16::from(self.read_u8());
+        let upper = u16::from(self.read_u8());
+        upper << 8 | lower
+    }
+
+    pub fn read_u32(&mut self) -> u32 {
+        let lower = u32::from(self.read_u16());
+        let upper = u32::from(self.read_u16());
+        upper << 16 | lower
+    }
+
+    pub fn read_u64(&mut self) -> u64 {
+        let lower = u64::from(self.read_u32());
+        let upper = u64::from(self.read_u32());
+        upper << 32 | lower
+    }
+
+    pub fn read_usize(&mut self) -> usize {
+        self.read_u64() as usize
+    }
+
+    // Fills `dest` with the stored bytes, extra bytes on either side are ignored.
+    pub fn read_bytes(&mut self, dest: &mut [u8]) {
+        let len = self.read_u32() as usize;
+        for (i, b) in dest.iter_mut().enumerate().take(len) {
+            *b = self.buf.get(self.pos + i).cloned().unwrap_or(0);
+        }
+        self.pos += len;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_sample(rom_hash: u64) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        writer.write_u8(0x12);
+        writer.write_bool(true);
+        writer.write_u16(0x3456);
+        writer.write_usize(0x789A_BCDE);
+        writer.write_u32(0x0102_0304);
+        writer.write_bytes(&[1, 2, 3]);
+        writer.finish(rom_hash)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let buf = write_sample(0xABCD);
+        let mut reader = StateReader::open(&buf, 0xABCD).unwrap();
+        assert_eq!(reader.read_u8(), 0x12);
+        assert!(reader.read_bool());
+        assert_eq!(reader.read_u16(), 0x3456);
+        assert_eq!(reader.read_usize(), 0x789A_BCDE);
+        assert_eq!(reader.read_u32(), 0x0102_0304);
+        let mut bytes = [0; 3];
+        reader.read_bytes(&mut bytes);
+        assert_eq!(bytes, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_reject_invalid_header() {
+        let mut buf = write_sample(0xABCD);
+        assert_eq!(StateReader::open(&buf, 0x1234).unwrap_err(), StateError::RomMismatch);
+        assert_eq!(StateReader::open(&buf[0..8], 0xABCD).unwrap_err(), StateError::InvalidMagic);
+        let last = buf.len() - 1;
+        buf[last] ^= 0xFF;
+        assert_eq!(StateReader::open(&buf, 0xABCD).unwrap_err(), StateError::Corrupted);
+        buf[4] = 0xFF;
+        assert_eq!(
+            StateReader::open(&buf, 0xABCD).unwrap_err(),
+            StateError::UnsupportedVersion(0xFF)
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioFormat {
+    // 16bit integer WAV
+    Wav,
+    // 32bit float WAV
+    WavFloat,
+    // Headerless little endian samples
+    RawFloat,
+    RawInt16,
+}
+
+impl AudioFormat {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "wav" => Ok(AudioFormat::Wav),
+            "wav-f32" => Ok(AudioFormat::WavFloat),
+            "f32" => Ok(AudioFormat::RawFloat),
+            "s16" => Ok(AudioFormat::RawInt16),
+            _ => Err(format!("invalid audio format {}", text)),
+        }
+    }
+
+    fn is_wav(self) -> bool {
+        self == AudioFormat::Wav || self == AudioFormat::WavFloat
+    }
+
+    fn is_float(self) -> bool {
+        self == AudioFormat::WavFloat || self == AudioFormat::RawFloat
+    }
+
+    fn get_sample_size(self) -> u32 {
+        if self.is_float() { 4 } else { 2 }
+    }
+
+    // Float samples need the extended fmt chunk and the fact chunk.
+    fn get_header_size(self) -> u32 {
+        if self.is_float() { 58 } else { 44 }
+    }
+}
+
+const WAV_FORMAT_PCM: u16 = 1;
+const WAV_FORMAT_IEEE_FLOAT: u16 = 3;
+
+// Writes mono samples of -1.0 to 1.0, the sizes in the WAV header are filled by `finish`.
+//
+// | offset | size | description                          |
+// |--------|------|--------------------------------------|
+// | 0x00   | 4    | "RIFF"                               |
+// | 0x04   | 4    | file size - 8                        |
+// | 0x08   | 4    | "WAVE"                               |
+// | 0x0C   | 4    | "fmt "                               |
+// | 0x10   | 4    | 16: integer, 18: float               |
+// | 0x14   | 2    | 1: integer, 3: float                 |
+// | 0x16   | 2    | channels                             |
+// | 0x18   | 4    | sample rate                          |
+// | 0x1C   | 4    | bytes per second                     |
+// | 0x20   | 2    | bytes per frame                      |
+// | 0x22   | 2    | bits per sample                      |
+// | 0x24   | 4    | "data"                               |
+// | 0x28   | 4    | data size                            |
+// | 0x2C   | -    | samples                              |
+//
+// Float samples have the extension size and the fact chunk after bits per sample.
+//
+// | offset | size | description                          |
+// |--------|------|--------------------------------------|
+// | 0x24   | 2    | 0                                    |
+// | 0x26   | 4    | "fact"                               |
+// | 0x2A   | 4    | 4                                    |
+// | 0x2E   | 4    | number of samples                    |
+// | 0x32   | 4    | "data"                               |
+// | 0x36   | 4    | data size                            |
+// | 0x3A   | -    | samples                              |
+pub struct AudioWriter<W: Write + Seek> {
+    writer: W,
+    format: AudioFormat,
+    data_size: u32,
+}
+
+impl AudioWriter<BufWriter<File>> {
+    pub fn create(path: &str, format: AudioFormat, sample_rate: u32) -> io::Result<Self> {
+        AudioWriter::new(BufWriter::new(File::create(path)?), format, sample_rate)
+    }
+}
+
+impl<W: Write + Seek> AudioWriter<W> {
+    pub fn new(writer: W, format: AudioFormat, sample_rate: u32) -> io::Result<Self> {
+        let mut audio = AudioWriter {
+            writer,
+            format,
+            data_size: 0,
+        };
+        if format.is_wav() {
+            audio.write_wav_header(sample_rate)?;
+        }
+        Ok(audio)
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(samples.len() * self.format.get_sample_size() as usize);
+        for sample in samples {
+            let sample = sample.clamp(-1.0, 1.0);
+            if self.format.is_float() {
+                buf.extend_from_slice(&sample.to_le_bytes());
+            } else {
+                buf.extend_from_slice(&((sample * 32767.0).round() as i16).to_le_bytes());
+            }
+        }
+        self.data_size += buf.len() as u32;
+        self.writer.write_all(&buf)
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.format.is_wav() {
+            let header_size = self.format.get_header_size();
+            self.writer.seek(SeekFrom::Start(0x04))?;
+            self.writer.write_all(&(header_size - 8 + self.data_size).to_le_bytes())?;
+            if self.format.is_float() {
+                let sample_count = self.data_size / self.format.get_sample_size();
+                self.writer.seek(SeekFrom::Start(0x2E))?;
+                self.writer.write_all(&sample_count.to_le_bytes())?;
+            }
+            self.writer.seek(SeekFrom::Start(header_size as u64 - 4))?;
+            self.writer.write_all(&self.data_size.to_le_bytes())?;
+            self.writer.seek(SeekFrom::End(0))?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_wav_header(&mut self, sample_rate: u32) -> io::Result<()> {
+        let sample_size = self.format.get_sample_size();
+        let tag = if self.format.is_float() {
+            WAV_FORMAT_IEEE_FLOAT
+        } else {
+            WAV_FORMAT_PCM
+        };
+        let header_size = self.format.get_header_size();
+        let fmt_size: u32 = if self.format.is_float() { 18 } else { 16 };
+        let mut header = Vec::with_capacity(header_size as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(header_size - 8).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&fmt_size.to_le_bytes());
+        header.extend_from_slice(&tag.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * sample_size).to_le_bytes());
+        header.extend_from_slice(&(sample_size as u16).to_le_bytes());
+        header.extend_from_slice(&(sample_size as u16 * 8).to_le_bytes());
+        if self.format.is_float() {
+            header.extend_from_slice(&0u16.to_le_bytes());
+            header.extend_from_slice(b"fact");
+            header.extend_from_slice(&4u32.to_le_bytes());
+            header.extend_from_slice(&0u32.to_le_bytes());
+        }
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        self.writer.write_all(&header)
+    }
+}
+
+// "song.wav" to "song.square1.wav"
+pub fn get_stem_path(path: &str, name: &str) -> String {
+    let file_start = path.rfind('/').map_or(0, |i| i + 1);
+    match path[file_start..].rfind('.') {
+        Some(i) if i > 0 => {
+            let i = file_start + i;
+            format!("{}.{}{}", &path[..i], name, &path[i..])
+        }
+        _ => format!("{}.{}", path, name),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_write_wav() {
+        let mut audio = AudioWriter::new(Cursor::new(Vec::new()), AudioFormat::Wav, 44_100).unwrap();
+        audio.write_samples(&[0.0, 1.0, -2.0]).unwrap();
+        let buf = audio.finish().unwrap().into_inner();
+        assert_eq!(buf.len(), 44 + 6);
+        assert_eq!(&buf[0..4], b"RIFF");
+        assert_eq!(&buf[4..8], &(36u32 + 6).to_le_bytes());
+        assert_eq!(&buf[20..22], &1u16.to_le_bytes());
+        assert_eq!(&buf[24..28], &44_100u32.to_le_bytes());
+        assert_eq!(&buf[34..36], &16u16.to_le_bytes());
+        assert_eq!(&buf[40..44], &6u32.to_le_bytes());
+        // Clamped to -1.0
+        assert_eq!(&buf[44..], &[0x00, 0x00, 0xFF, 0x7F, 0x01, 0x80]);
+
+        let mut audio = AudioWriter::new(Cursor::new(Vec::new()), AudioFormat::WavFloat, 44_100).unwrap();
+        audio.write_samples(&[0.5, -0.5]).unwrap();
+        let buf = audio.finish().unwrap().into_inner();
+        assert_eq!(buf.len(), 58 + 8);
+        assert_eq!(&buf[4..8], &(50u32 + 8).to_le_bytes());
+        assert_eq!(&buf[16..20], &18u32.to_le_bytes());
+        assert_eq!(&buf[20..22], &3u16.to_le_bytes());
+        assert_eq!(&buf[34..36], &32u16.to_le_bytes());
+        assert_eq!(&buf[36..38], &0u16.to_le_bytes());
+        assert_eq!(&buf[38..42], b"fact");
+        assert_eq!(&buf[42..46], &4u32.to_le_bytes());
+        assert_eq!(&buf[46..50], &2u32.to_le_bytes());
+        assert_eq!(&buf[50..54], b"data");
+        assert_eq!(&buf[54..58], &8u32.to_le_bytes());
+        assert_eq!(&buf[58..62], &0.5f32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_write_raw_float() {
+        let mut audio = AudioWriter::new(Cursor::new(Vec::new()), AudioFormat::RawFloat, 48_000).unwrap();
+        audio.write_samples(&[0.5]).unwrap();
+        assert_eq!(audio.finish().unwrap().into_inner(), 0.5f32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_get_stem_path() {
+        assert_eq!(get_stem_path("out/song.wav", "dmc"), "out/song.dmc.wav");
+        assert_eq!(get_stem_path("out.d/song", "dmc"), "out.d/song.dmc");
+        assert_eq!(get_stem_path(".song", "dmc"), ".song.dmc");
+    }
+}
@@ -0,0 +1,46 @@
+use std::fs::File;
+use std::io::{self, Write};
+
+// Writes the RGBA frame buffer as a binary PPM (P6) image.
+pub fn write_frame(path: &str, frame: &[u8], width: usize, height: usize) -> io::Result<()> {
+    let mut buf = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for pixel in frame.chunks(4).take(width * height) {
+        buf.extend_from_slice(&pixel[0..3]);
+    }
+    File::create(path)?.write_all(&buf)
+}
+
+pub fn write_ram(path: &str, ram: &[u8]) -> io::Result<()> {
+    File::create(path)?.write_all(format_hex(ram).as_bytes())
+}
+
+// 16 bytes a line with the address on the left.
+//
+// 0000: 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F
+fn format_hex(ram: &[u8]) -> String {
+    ram.chunks(16)
+        .enumerate()
+        .map(|(i, line)| {
+            let bytes = line
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<String>>()
+                .join(" ");
+            format!("{:04X}: {}\n", i * 16, bytes)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_hex() {
+        let ram: Vec<u8> = (0..18).collect();
+        assert_eq!(
+            format_hex(&ram),
+            "0000: 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F\n0010: 10 11\n"
+        );
+    }
+}
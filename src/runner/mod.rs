@@ -0,0 +1,410 @@
+mod audio;
+mod dump;
+mod script;
+
+use self::audio::{AudioFormat, AudioWriter};
+use self::script::Script;
+use rustynes::nes::{self, Channel, Context, MixerSettings, Overscan, CHANNELS, DEFAULT_SAMPLE_RATE};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const DEFAULT_FRAMES: usize = 60;
+
+const USAGE: &str = "usage: rustynes <rom.nes|music.nsf> [options]
+
+options:
+  --frames <n>            frames to run, the limit when --until is given (default 60)
+  --until <addr>=<value>  stop when the memory has the value, use != to stop when it differs (hex)
+  --input <file>          scripted input, see src/runner/script.rs for the format
+  --frame-image <file>    write the last frame as a PPM image
+  --overscan <t,b,l,r>    pixels to crop from each edge of --frame-image (default 0,0,0,0)
+  --ram-hex <file>        write the work ram as a hex dump
+  --sav <file>            load battery backed ram before running and store it after
+  --sprite-limit <on|off> draw up to 8 sprites on a line as the hardware does (default on)
+  --audio <file>          record the audio
+  --audio-format <format> wav, wav-f32, or headerless f32 or s16 little endian samples (default wav)
+  --audio-frames <s-e>    record from frame s until before frame e (default all frames)
+  --audio-stems <on|off>  also record each channel alone to <file>.<channel>.<ext> (default off)
+  --sample-rate <hz>      sample rate of --audio (default 44100)
+  --mute <channels>       channels to silence, e.g. square2,noise (square1, square2, triangle, noise, dmc)
+  --solo <channels>       channels to hear alone, the stems are not affected by --mute and --solo
+  --track <n>             track of the *.nsf file to play from 1 (default the starting track of the file)";
+
+#[derive(Debug, PartialEq)]
+enum Condition {
+    Equal(u16, u8),
+    NotEqual(u16, u8),
+}
+
+impl Condition {
+    fn parse(text: &str) -> Result<Self, String> {
+        let (addr, value, is_equal) = if let Some(i) = text.find("!=") {
+            (&text[..i], &text[i + 2..], false)
+        } else if let Some(i) = text.find('=') {
+            (&text[..i], &text[i + 1..], true)
+        } else {
+            return Err(format!("invalid condition {}", text));
+        };
+        let addr = u16::from_str_radix(addr.trim_start_matches('$'), 16)
+            .map_err(|_| format!("invalid address {}", addr))?;
+        let value = u8::from_str_radix(value.trim_start_matches('$'), 16)
+            .map_err(|_| format!("invalid value {}", value))?;
+        Ok(if is_equal {
+            Condition::Equal(addr, value)
+        } else {
+            Condition::NotEqual(addr, value)
+        })
+    }
+
+    fn is_met(&self, ctx: &Context) -> bool {
+        match *self {
+            Condition::Equal(addr, value) => ctx.peek(addr) == value,
+            Condition::NotEqual(addr, value) => ctx.peek(addr) != value,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    rom: String,
+    frames: usize,
+    until: Option<Condition>,
+    input: Option<String>,
+    frame_image: Option<String>,
+    overscan: Overscan,
+    ram_hex: Option<String>,
+    sav: Option<String>,
+    is_sprite_limited: bool,
+    audio: Option<String>,
+    audio_format: AudioFormat,
+    audio_frames: Option<(usize, usize)>,
+    has_audio_stems: bool,
+    sample_rate: u32,
+    mixer_settings: MixerSettings,
+    track: Option<usize>,
+}
+
+// Returns the exit code.
+// 0: finished, 1: the condition of --until is not met in the frames, 2: error.
+pub fn run(args: Vec<String>) -> i32 {
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    match execute(&options) {
+        Ok(true) => 0,
+        Ok(false) => {
+            eprintln!("condition is not met in {} frames", options.frames);
+            1
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            2
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        rom: String::new(),
+        frames: DEFAULT_FRAMES,
+        until: None,
+        input: None,
+        frame_image: None,
+        overscan: Overscan::none(),
+        ram_hex: None,
+        sav: None,
+        is_sprite_limited: true,
+        audio: None,
+        audio_format: AudioFormat::Wav,
+        audio_frames: None,
+        has_audio_stems: false,
+        sample_rate: DEFAULT_SAMPLE_RATE,
+        mixer_settings: MixerSettings::new(),
+        track: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if !options.rom.is_empty() {
+                return Err(format!("unexpected argument {}", arg));
+            }
+            options.rom = arg.clone();
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or(format!("{} requires a value", arg))?
+            .clone();
+        match arg.as_str() {
+            "--frames" => {
+                options.frames = value
+                    .parse()
+                    .map_err(|_| format!("invalid frames {}", value))?
+            }
+            "--until" => options.until = Some(Condition::parse(&value)?),
+            "--input" => options.input = Some(value),
+            "--frame-image" => options.frame_image = Some(value),
+            "--overscan" => options.overscan = parse_overscan(&value)?,
+            "--ram-hex" => options.ram_hex = Some(value),
+            "--sav" => options.sav = Some(value),
+            "--sprite-limit" => {
+                options.is_sprite_limited = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(format!("invalid sprite limit {}", value)),
+                }
+            }
+            "--audio" => options.audio = Some(value),
+            "--audio-format" => options.audio_format = AudioFormat::parse(&value)?,
+            "--audio-frames" => options.audio_frames = Some(parse_frame_range(&value)?),
+            "--audio-stems" => {
+                options.has_audio_stems = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(format!("invalid audio stems {}", value)),
+                }
+            }
+            "--sample-rate" => {
+                options.sample_rate = match value.parse() {
+                    Ok(rate) if rate > 0 => rate,
+                    _ => return Err(format!("invalid sample rate {}", value)),
+                }
+            }
+            "--mute" => {
+                for channel in parse_channels(&value)? {
+                    options.mixer_settings.set_muted(channel, true);
+                }
+            }
+            "--solo" => {
+                for channel in parse_channels(&value)? {
+                    options.mixer_settings.set_soloed(channel, true);
+                }
+            }
+            "--track" => {
+                options.track = match value.parse::<usize>() {
+                    Ok(track) if track > 0 => Some(track - 1),
+                    _ => return Err(format!("invalid track {}", value)),
+                }
+            }
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+    if options.rom.is_empty() {
+        return Err("no rom is given".to_string());
+    }
+    Ok(options)
+}
+
+// "top,bottom,left,right"
+fn parse_overscan(text: &str) -> Result<Overscan, String> {
+    let values = text
+        .split(',')
+        .map(|v| v.trim().parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| format!("invalid overscan {}", text))?;
+    if values.len() != 4 {
+        return Err(format!("invalid overscan {}", text));
+    }
+    let overscan = Overscan {
+        top: values[0],
+        bottom: values[1],
+        left: values[2],
+        right: values[3],
+    };
+    let (width, height) = overscan.get_size();
+    if width == 0 || height == 0 {
+        return Err(format!("overscan {} crops the whole frame", text));
+    }
+    Ok(overscan)
+}
+
+// "start-end", the end is exclusive.
+fn parse_frame_range(text: &str) -> Result<(usize, usize), String> {
+    let values = text
+        .split('-')
+        .map(|v| v.trim().parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| format!("invalid frame range {}", text))?;
+    match values[..] {
+        [start, end] if start < end => Ok((start, end)),
+        _ => Err(format!("invalid frame range {}", text)),
+    }
+}
+
+// "square1,noise"
+fn parse_channels(text: &str) -> Result<Vec<Channel>, String> {
+    text.split(',')
+        .map(|name| Channel::from_name(name.trim()).ok_or(format!("unknown channel {}", name)))
+        .collect()
+}
+
+// The mix and the stems of each channel when enabled.
+struct Recorder {
+    mix: AudioWriter<io::BufWriter<File>>,
+    stems: Vec<(Channel, AudioWriter<io::BufWriter<File>>)>,
+}
+
+impl Recorder {
+    fn create(path: &str, options: &Options) -> io::Result<Self> {
+        let create = |path: &str| AudioWriter::create(path, options.audio_format, options.sample_rate);
+        let mut stems = Vec::new();
+        if options.has_audio_stems {
+            for channel in CHANNELS.iter() {
+                stems.push((*channel, create(&audio::get_stem_path(path, channel.get_name()))?));
+            }
+        }
+        Ok(Recorder {
+            mix: create(path)?,
+            stems,
+        })
+    }
+
+    fn write(&mut self, ctx: &Context) -> io::Result<()> {
+        self.mix.write_samples(ctx.get_audio_samples())?;
+        for &mut (channel, ref mut stem) in &mut self.stems {
+            stem.write_samples(ctx.get_stem_samples(channel))?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        self.mix.finish()?;
+        for (_, stem) in self.stems {
+            stem.finish()?;
+        }
+        Ok(())
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut buf))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    Ok(buf)
+}
+
+// Returns whether the condition is met, always true without --until.
+fn execute(options: &Options) -> Result<bool, String> {
+    let mut rom = read_file(&options.rom)?;
+    let script = match options.input {
+        Some(ref path) => Script::parse(&String::from_utf8_lossy(&read_file(path)?))?,
+        None => Script::new(),
+    };
+    let mut ctx = Context::new(&mut rom).map_err(|e| format!("{}: {}", options.rom, e))?;
+    ctx.set_sprite_limit(options.is_sprite_limited);
+    ctx.set_sample_rate(options.sample_rate);
+    ctx.set_stems_enabled(options.audio.is_some() && options.has_audio_stems);
+    ctx.set_mixer_settings(options.mixer_settings.clone());
+    let mut recorder = match options.audio {
+        Some(ref path) => Some(Recorder::create(path, options).map_err(|e| format!("{}: {}", path, e))?),
+        None => None,
+    };
+    let (audio_start, audio_end) = options.audio_frames.unwrap_or((0, options.frames));
+    if let Some(ref path) = options.sav {
+        // A missing *.sav is fine, it is created after the first run.
+        if Path::new(path).exists() {
+            ctx.load_battery_ram(&read_file(path)?);
+        }
+    }
+    nes::reset(&mut ctx);
+    if let Some(nsf) = ctx.get_nsf() {
+        if nsf.expansion_chips != 0 {
+            eprintln!("expansion sound chips are not supported, their channels are silent");
+        }
+    }
+    if let Some(track) = options.track {
+        match ctx.get_nsf().map(|nsf| nsf.track_count) {
+            Some(count) if track < count => ctx.select_track(track),
+            Some(count) => return Err(format!("track {} is out of 1-{}", track + 1, count)),
+            None => return Err("--track is only for *.nsf files".to_string()),
+        }
+    }
+    let mut is_met = options.until.is_none();
+    for frame in 0..options.frames {
+        nes::run(&mut ctx, script.get_key_state(frame));
+        if let Some(ref mut recorder) = recorder {
+            if frame >= audio_start && frame < audio_end {
+                recorder.write(&ctx).map_err(|e| format!("audio: {}", e))?;
+            }
+        }
+        if let Some(ref condition) = options.until {
+            if condition.is_met(&ctx) {
+                is_met = true;
+                break;
+            }
+        }
+    }
+    if let Some(recorder) = recorder {
+        recorder.finish().map_err(|e| format!("audio: {}", e))?;
+    }
+    if let Some(ref path) = options.frame_image {
+        let (width, height) = options.overscan.get_size();
+        let frame = options.overscan.crop(ctx.get_frame_buffer());
+        dump::write_frame(path, &frame, width, height).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    if let Some(ref path) = options.ram_hex {
+        dump::write_ram(path, ctx.get_work_ram()).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    if let (Some(path), Some(ram)) = (options.sav.as_ref(), ctx.get_battery_ram()) {
+        File::create(path)
+            .and_then(|mut file| file.write_all(ram))
+            .map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(is_met)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options =
+            parse_args(&to_args(&["game.nes", "--frames", "300", "--until", "6000!=80"])).unwrap();
+        assert_eq!(options.rom, "game.nes");
+        assert_eq!(options.frames, 300);
+        assert_eq!(options.until, Some(Condition::NotEqual(0x6000, 0x80)));
+        assert_eq!(options.frame_image, None);
+        assert!(options.is_sprite_limited);
+        let options = parse_args(&to_args(&["game.nes", "--sprite-limit", "off"])).unwrap();
+        assert!(!options.is_sprite_limited);
+        assert!(parse_args(&to_args(&["game.nes", "--sprite-limit", "no"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_audio_args() {
+        let options = parse_args(&to_args(&[
+            "game.nes", "--audio", "song.wav", "--audio-frames", "60-120", "--mute", "square2,dmc",
+        ])).unwrap();
+        assert_eq!(options.audio, Some("song.wav".to_string()));
+        assert_eq!(options.audio_format, AudioFormat::Wav);
+        assert_eq!(options.audio_frames, Some((60, 120)));
+        assert!(options.mixer_settings.is_muted(Channel::Dmc));
+        assert!(!options.mixer_settings.is_muted(Channel::Square1));
+        assert!(parse_args(&to_args(&["game.nes", "--solo", "pulse"])).is_err());
+        assert!(parse_args(&to_args(&["game.nes", "--audio-frames", "120-60"])).is_err());
+        assert!(parse_args(&to_args(&["game.nes", "--audio-format", "mp3"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_overscan() {
+        assert_eq!(parse_overscan("8,8,0,0"), Ok(Overscan::ntsc()));
+        assert!(parse_overscan("8,8").is_err());
+        assert!(parse_overscan("240,0,0,0").is_err());
+    }
+
+    #[test]
+    fn test_reject_missing_rom() {
+        assert!(parse_args(&to_args(&["--frames", "10"])).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+// Scripted input for the runner.
+//
+// Each line is `<frame> <buttons>`, the buttons are held from the frame until the next line.
+// Buttons are separated by `,` and `-` releases all of them. `#` starts a comment.
+//
+//   # press start once the title is shown
+//   0   -
+//   120 START
+//   122 -
+//   200 A,RIGHT
+
+const BUTTONS: [(&str, u8); 8] = [
+    ("A", 0x01),
+    ("B", 0x02),
+    ("SELECT", 0x04),
+    ("START", 0x08),
+    ("UP", 0x10),
+    ("DOWN", 0x20),
+    ("LEFT", 0x40),
+    ("RIGHT", 0x80),
+];
+
+#[derive(Debug, PartialEq)]
+pub struct Script {
+    // Sorted by frame.
+    entries: Vec<(usize, u8)>,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Script {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut columns = line.split_whitespace();
+            let frame = columns
+                .next()
+                .and_then(|frame| frame.parse::<usize>().ok())
+                .ok_or(format!("line {}: invalid frame", i + 1))?;
+            let buttons = columns.next().unwrap_or("-");
+            let key_state = parse_buttons(buttons).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            entries.push((frame, key_state));
+        }
+        entries.sort_by_key(|&(frame, _)| frame);
+        Ok(Script { entries })
+    }
+
+    pub fn get_key_state(&self, frame: usize) -> u8 {
+        self.entries
+            .iter()
+            .take_while(|&&(start, _)| start <= frame)
+            .last()
+            .map_or(0, |&(_, key_state)| key_state)
+    }
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Script::new()
+    }
+}
+
+fn parse_buttons(buttons: &str) -> Result<u8, String> {
+    if buttons == "-" {
+        return Ok(0);
+    }
+    buttons.split(',').try_fold(0, |key_state, name| {
+        BUTTONS
+            .iter()
+            .find(|&&(button, _)| button.eq_ignore_ascii_case(name))
+            .map(|&(_, bit)| key_state | bit)
+            .ok_or(format!("unknown button {}", name))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hold_buttons_until_next_line() {
+        let script = Script::parse("# title\n10 START\n12 -\n20 a,Right\n").unwrap();
+        assert_eq!(script.get_key_state(0), 0x00);
+        assert_eq!(script.get_key_state(11), 0x08);
+        assert_eq!(script.get_key_state(12), 0x00);
+        assert_eq!(script.get_key_state(100), 0x81);
+    }
+
+    #[test]
+    fn test_reject_unknown_button() {
+        assert!(Script::parse("0 TURBO").is_err());
+    }
+}
@@ -0,0 +1,57 @@
+extern crate rustynes;
+
+use rustynes::nes::{self, Context};
+use std::fs::File;
+use std::io::Read;
+
+// The tests write $80 to $6000 while running, then 0 when passed or the number of the failed check.
+// $6001-$6003 hold $DE $B0 $61 once $6000 is valid, and $6004- the text of the result.
+// See roms/apu_test/readme.txt for the meaning of each number.
+const STATUS_ADDR: u16 = 0x6000;
+const TEXT_ADDR: u16 = 0x6004;
+const RUNNING: u8 = 0x80;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const MAX_FRAMES: usize = 300;
+
+fn read_text(ctx: &Context) -> String {
+    (TEXT_ADDR..0x7FFF)
+        .map(|addr| ctx.peek(addr))
+        .take_while(|c| *c != 0)
+        .map(|c| c as char)
+        .collect()
+}
+
+fn run_test(name: &str) {
+    let mut rom = Vec::new();
+    File::open(format!("roms/apu_test/{}.nes", name))
+        .unwrap()
+        .read_to_end(&mut rom)
+        .unwrap();
+    let mut ctx = Context::new(&mut rom).unwrap();
+    nes::reset(&mut ctx);
+    for _ in 0..MAX_FRAMES {
+        nes::run(&mut ctx, 0);
+        let signature = [ctx.peek(0x6001), ctx.peek(0x6002), ctx.peek(0x6003)];
+        let status = ctx.peek(STATUS_ADDR);
+        if signature == SIGNATURE && status != RUNNING {
+            assert!(status == 0, "{} failed #{}\n{}", name, status, read_text(&ctx));
+            return;
+        }
+    }
+    panic!("{} didn't finish\n{}", name, read_text(&ctx));
+}
+
+#[test]
+fn test_len_ctr() {
+    run_test("1-len_ctr");
+}
+
+#[test]
+fn test_irq_flag() {
+    run_test("2-irq_flag");
+}
+
+#[test]
+fn test_frame_timing() {
+    run_test("3-frame_timing");
+}
@@ -0,0 +1,131 @@
+extern crate rustynes;
+
+use rustynes::nes::{self, Context};
+use std::fs::File;
+use std::io::Read;
+
+fn load(path: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    File::open(path).unwrap().read_to_end(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn test_run_without_frontend() {
+    let mut rom = load("roms/hello.nes");
+    let mut ctx = Context::new(&mut rom).unwrap();
+    nes::reset(&mut ctx);
+    for _ in 0..60 {
+        nes::run(&mut ctx, 0);
+    }
+    let frame = ctx.get_frame_buffer();
+    assert_eq!(frame.len(), 256 * 240 * 4);
+    // "HELLO, WORLD!" leaves some pixels different from the backdrop.
+    let backdrop = &frame[0..3];
+    assert!(frame.chunks(4).any(|pixel| &pixel[0..3] != backdrop));
+}
+
+fn run_frames(ctx: &mut Context, frames: usize) {
+    for frame in 0..frames {
+        nes::run(ctx, if frame % 8 == 0 { 0x01 } else { 0x00 });
+    }
+}
+
+#[test]
+fn test_load_state_replays_the_same_frames() {
+    let mut rom = load("roms/falling.nes");
+    let mut ctx = Context::new(&mut rom).unwrap();
+    nes::reset(&mut ctx);
+    run_frames(&mut ctx, 30);
+    let state = ctx.save_state();
+    run_frames(&mut ctx, 30);
+    let frame = ctx.get_frame_buffer().to_vec();
+    let ram = ctx.get_work_ram().to_vec();
+
+    ctx.load_state(&state).unwrap();
+    run_frames(&mut ctx, 30);
+    assert_eq!(ctx.get_frame_buffer(), &frame[..]);
+    assert_eq!(ctx.get_work_ram(), &ram[..]);
+    assert_eq!(ctx.save_state().len(), state.len());
+}
+
+#[test]
+fn test_reject_state_of_another_rom() {
+    let mut hello = load("roms/hello.nes");
+    let mut falling = load("roms/falling.nes");
+    let state = Context::new(&mut hello).unwrap().save_state();
+    let mut ctx = Context::new(&mut falling).unwrap();
+    assert_eq!(ctx.load_state(&state), Err(nes::StateError::RomMismatch));
+}
+
+#[test]
+fn test_rewind_steps_back_frame_by_frame() {
+    let mut rom = load("roms/falling.nes");
+    let mut ctx = Context::new(&mut rom).unwrap();
+    let mut rewind = nes::Rewind::new(1, 16 * 1024 * 1024);
+    nes::reset(&mut ctx);
+    let mut states = Vec::new();
+    for frame in 0..90 {
+        nes::run(&mut ctx, if frame % 8 == 0 { 0x01 } else { 0x00 });
+        rewind.push(&ctx);
+        states.push(ctx.save_state());
+    }
+    assert_eq!(rewind.len(), 90);
+    for expected in states.iter().rev().skip(1) {
+        assert!(rewind.step_back(&mut ctx));
+        assert_eq!(&ctx.save_state(), expected);
+    }
+    assert!(!rewind.step_back(&mut ctx));
+}
+
+#[test]
+fn test_rewind_keeps_memory_budget() {
+    let mut rom = load("roms/falling.nes");
+    let mut ctx = Context::new(&mut rom).unwrap();
+    let budget = 64 * 1024;
+    let mut rewind = nes::Rewind::new(1, budget);
+    nes::reset(&mut ctx);
+    for _ in 0..600 {
+        nes::run(&mut ctx, 0);
+        rewind.push(&ctx);
+    }
+    assert!(rewind.get_memory_usage() <= budget);
+    assert!(rewind.len() > 1);
+    while rewind.step_back(&mut ctx) {}
+    nes::run(&mut ctx, 0);
+}
+
+#[test]
+fn test_generate_audio_samples_and_stems() {
+    let mut rom = load("roms/dushlan.nes");
+    let mut ctx = Context::new(&mut rom).unwrap();
+    ctx.set_stems_enabled(true);
+    nes::reset(&mut ctx);
+    let mut has_dmc = false;
+    for frame in 0..120 {
+        nes::run(&mut ctx, 0);
+        let len = ctx.get_audio_samples().len();
+        // 44100Hz at 60.1 frames a second, the first frame is cut short by the reset.
+        if frame > 0 {
+            assert!(len == 733 || len == 734, "{} samples in frame {}", len, frame);
+        }
+        for channel in nes::CHANNELS.iter() {
+            assert_eq!(ctx.get_stem_samples(*channel).len(), len);
+        }
+        has_dmc |= ctx.get_stem_samples(nes::Channel::Dmc).iter().any(|s| s.abs() > 0.01);
+    }
+    assert!(has_dmc);
+}
+
+#[test]
+fn test_reject_broken_rom() {
+    assert!(Context::new(&mut [0; 0x10]).is_err());
+    let mut rom = load("roms/hello.nes");
+    assert!(Context::new(&mut rom[0..0x4000]).is_err());
+    // Mapper 15
+    rom[6] |= 0xF0;
+    match Context::new(&mut rom) {
+        Err(e) => assert_eq!(e, nes::RomError::UnsupportedMapper(15)),
+        Ok(_) => panic!("mapper 15 is loaded"),
+    }
+}
@@ -0,0 +1,45 @@
+extern crate rustynes;
+
+use rustynes::nes::{self, Context};
+use std::fs::File;
+use std::io::Read;
+
+const NESTEST_ROM: &str = "roms/nestest.nes";
+// Nintendulator's trace of nestest in the automation mode, compared line by line.
+const NESTEST_LOG: &str = "roms/nestest.log";
+
+fn load(path: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    File::open(path)
+        .unwrap_or_else(|e| panic!("{}: {}", path, e))
+        .read_to_end(&mut buf)
+        .unwrap();
+    buf
+}
+
+#[test]
+fn test_nestest() {
+    let mut rom = load(NESTEST_ROM);
+    let log = String::from_utf8(load(NESTEST_LOG)).unwrap();
+    let golden = log.lines().map(|line| line.trim_end()).collect::<Vec<&str>>();
+    let mut ctx = Context::new(&mut rom).unwrap();
+    nes::reset(&mut ctx);
+    // The automation mode starts at 0xC000 and runs without PPU.
+    ctx.set_program_counter(0xC000);
+    let mut lines = Vec::new();
+    while lines.len() < golden.len() {
+        nes::step_with_trace(&mut ctx, |line| lines.push(line.trim_end().to_string()));
+    }
+    for (i, (actual, expected)) in lines.iter().zip(golden.iter()).enumerate() {
+        assert!(
+            actual == expected,
+            "trace diverged at line {}\nexpected: {}\n  actual: {}",
+            i + 1,
+            expected,
+            actual
+        );
+    }
+    // nestest stores the number of the first failed test, 0x00 means passed.
+    assert_eq!(ctx.peek(0x0002), 0x00, "official opecode test failed");
+    assert_eq!(ctx.peek(0x0003), 0x00, "unofficial opecode test failed");
+}
@@ -0,0 +1,85 @@
+extern crate rustynes;
+
+use rustynes::nes::{self, Context};
+
+// INIT stores the track at $00 and starts a square wave, PLAY counts its calls at $01.
+//
+// 8000: STA $00
+// 8002: LDA #$BF
+// 8004: STA $4000
+// 8007: LDA #$FD
+// 8009: STA $4002
+// 800C: LDA #$00
+// 800E: STA $4003
+// 8011: RTS
+// 8012: INC $01
+// 8014: RTS
+fn build_nsf() -> Vec<u8> {
+    let mut buf = vec![0; 0x80];
+    buf[0..5].copy_from_slice(b"NESM\x1A");
+    buf[0x05] = 1;
+    buf[0x06] = 4;
+    buf[0x07] = 3;
+    buf[0x08..0x0E].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x12, 0x80]);
+    buf[0x0E..0x12].copy_from_slice(b"Test");
+    // 60Hz
+    buf[0x6E..0x70].copy_from_slice(&16_639u16.to_le_bytes());
+    buf.extend_from_slice(&[0x85, 0x00, 0xA9, 0xBF, 0x8D, 0x00, 0x40, 0xA9, 0xFD, 0x8D, 0x02, 0x40,
+                            0xA9, 0x00, 0x8D, 0x03, 0x40, 0x60, 0xE6, 0x01, 0x60]);
+    buf
+}
+
+#[test]
+fn test_play_nsf() {
+    let mut buf = build_nsf();
+    let mut ctx = Context::new(&mut buf).unwrap();
+    assert_eq!(ctx.get_nsf().unwrap().title, "Test");
+    nes::reset(&mut ctx);
+    assert_eq!(ctx.get_track(), 2);
+    let mut is_audible = false;
+    for _ in 0..60 {
+        nes::run(&mut ctx, 0);
+        is_audible |= ctx.get_audio_samples().iter().any(|s| s.abs() > 0.05);
+    }
+    assert_eq!(ctx.peek(0x0000), 2);
+    let count = ctx.peek(0x0001);
+    assert!((59..=61).contains(&count), "PLAY is called {} times", count);
+    assert!(is_audible);
+}
+
+#[test]
+fn test_select_track() {
+    let mut buf = build_nsf();
+    let mut ctx = Context::new(&mut buf).unwrap();
+    nes::reset(&mut ctx);
+    for _ in 0..10 {
+        nes::run(&mut ctx, 0);
+    }
+    ctx.select_track(0);
+    nes::run(&mut ctx, 0);
+    assert_eq!(ctx.peek(0x0000), 0);
+    assert!(ctx.peek(0x0001) <= 1);
+    // Out of range plays the last track.
+    ctx.select_track(10);
+    nes::run(&mut ctx, 0);
+    assert_eq!(ctx.get_track(), 3);
+    assert_eq!(ctx.peek(0x0000), 3);
+}
+
+#[test]
+fn test_load_state_restores_track() {
+    let mut buf = build_nsf();
+    let mut ctx = Context::new(&mut buf).unwrap();
+    nes::reset(&mut ctx);
+    ctx.select_track(1);
+    nes::run(&mut ctx, 0);
+    let state = ctx.save_state();
+    ctx.select_track(3);
+    ctx.load_state(&state).unwrap();
+    assert_eq!(ctx.get_track(), 1);
+    assert_eq!(ctx.save_state(), state);
+    // The driver keeps calling INIT with the restored track.
+    nes::reset(&mut ctx);
+    nes::run(&mut ctx, 0);
+    assert_eq!(ctx.peek(0x0000), 1);
+}
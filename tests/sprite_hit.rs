@@ -0,0 +1,84 @@
+extern crate rustynes;
+
+use rustynes::nes::{self, Context};
+use std::fs::File;
+use std::io::Read;
+
+// The tests keep the current test number at $F8 while running,
+// 1 when all passed and the number of the failed test otherwise.
+// See roms/sprite_hit_tests/readme.txt for the meaning of each number.
+const RESULT_ADDR: u16 = 0x00F8;
+const PASSED: u8 = 0x01;
+const MAX_FRAMES: usize = 300;
+
+fn run_test(name: &str) {
+    let mut rom = Vec::new();
+    File::open(format!("roms/sprite_hit_tests/{}.nes", name))
+        .unwrap()
+        .read_to_end(&mut rom)
+        .unwrap();
+    let mut ctx = Context::new(&mut rom).unwrap();
+    nes::reset(&mut ctx);
+    for _ in 0..MAX_FRAMES {
+        nes::run(&mut ctx, 0);
+        if ctx.peek(RESULT_ADDR) == PASSED {
+            return;
+        }
+    }
+    panic!("{} failed #{}", name, ctx.peek(RESULT_ADDR));
+}
+
+#[test]
+fn test_basics() {
+    run_test("01.basics");
+}
+
+#[test]
+fn test_alignment() {
+    run_test("02.alignment");
+}
+
+#[test]
+fn test_corners() {
+    run_test("03.corners");
+}
+
+#[test]
+fn test_flip() {
+    run_test("04.flip");
+}
+
+#[test]
+fn test_left_clip() {
+    run_test("05.left_clip");
+}
+
+#[test]
+fn test_right_edge() {
+    run_test("06.right_edge");
+}
+
+#[test]
+fn test_screen_bottom() {
+    run_test("07.screen_bottom");
+}
+
+#[test]
+fn test_double_height() {
+    run_test("08.double_height");
+}
+
+#[test]
+fn test_timing_basics() {
+    run_test("09.timing_basics");
+}
+
+#[test]
+fn test_timing_order() {
+    run_test("10.timing_order");
+}
+
+#[test]
+fn test_edge_timing() {
+    run_test("11.edge_timing");
+}